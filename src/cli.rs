@@ -21,6 +21,35 @@ pub fn initialize() -> App<'static, 'static> {
                 .takes_value(false)
                 .required(false),
         )
+        .arg(
+            Arg::with_name("update_snapshots")
+                .help("Rewrites the store snapshots with the current state of the store.")
+                .long("update-snapshots")
+                .short("u")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("dump_failed")
+                .help("Dumps the store of every failed test to the artifacts folder.")
+                .long("dump-failed")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("record_calls")
+                .help("Records the contract calls which are not mocked from the `rpcUrl` node.")
+                .long("record-calls")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("network")
+                .help("Takes the data source addresses of the network from the networks file.")
+                .long("network")
+                .takes_value(true)
+                .required(false),
+        )
         .arg(
             Arg::with_name("test_suites")
                 .help("Please specify the names of the test suites you would like to run.")
@@ -28,6 +28,7 @@ pub struct CompileOutput {
     pub stdout: Vec<u8>,
     pub stderr: Vec<u8>,
     pub file: PathBuf,
+    pub source: PathBuf,
 }
 
 #[allow(dead_code)]
@@ -105,7 +106,7 @@ impl Compiler {
                 } else {
                     logging::info!("{} skipped!", name.bright_blue());
 
-                    self.skip_compile(out_file)
+                    self.skip_compile(in_file, out_file)
                 };
 
                 (name, output)
@@ -119,7 +120,7 @@ impl Compiler {
 
     fn compile(&self, in_file: PathBuf, out_file: PathBuf) -> CompileOutput {
         let output = Command::new(&self.exec)
-            .args([in_file])
+            .args([&in_file])
             .arg(&self.global)
             .arg("--lib")
             .arg(&self.lib)
@@ -142,15 +143,17 @@ impl Compiler {
             stdout: output.stdout,
             stderr: output.stderr,
             file: out_file,
+            source: in_file,
         }
     }
 
-    fn skip_compile(&self, out_file: PathBuf) -> CompileOutput {
+    fn skip_compile(&self, in_file: PathBuf, out_file: PathBuf) -> CompileOutput {
         CompileOutput {
             status: ExitStatusExt::from_raw(0),
             stdout: vec![],
             stderr: vec![],
             file: out_file,
+            source: in_file,
         }
     }
 }
@@ -6,6 +6,16 @@ pub struct MatchstickConfig {
     pub libs_path: String,
     pub tests_path: String,
     pub manifest_path: String,
+    pub fixtures: Vec<String>,
+    pub mock_fixtures: Vec<String>,
+    pub artifacts_path: Option<String>,
+    pub strict_references: bool,
+    pub strict_mocks: bool,
+    pub rpc_url: Option<String>,
+    pub rpc_block: Option<u64>,
+    pub cassette_path: String,
+    pub network: Option<String>,
+    pub networks_path: String,
 }
 
 impl MatchstickConfig {
@@ -15,6 +25,16 @@ impl MatchstickConfig {
             libs_path: "./node_modules".to_owned(),
             tests_path: "./tests".to_owned(),
             manifest_path: "./subgraph.yaml".to_owned(),
+            fixtures: vec![],
+            mock_fixtures: vec![],
+            artifacts_path: None,
+            strict_references: false,
+            strict_mocks: false,
+            rpc_url: None,
+            rpc_block: None,
+            cassette_path: "./tests/.cassette.json".to_owned(),
+            network: None,
+            networks_path: "./networks.json".to_owned(),
         }
     }
 
@@ -33,6 +53,53 @@ impl MatchstickConfig {
                 parser::extract_string_or(&matchstick_yaml, "libsFolder", config.libs_path);
             config.manifest_path =
                 parser::extract_string_or(&matchstick_yaml, "manifestPath", config.manifest_path);
+            // The store of every failed test is dumped in the artifacts folder, if it is set
+            config.artifacts_path = Some(parser::extract_string_or(
+                &matchstick_yaml,
+                "artifactsFolder",
+                "".to_owned(),
+            ))
+            .filter(|path| !path.is_empty());
+            // Fixture files which are loaded into the store of every test suite
+            config.fixtures = parser::extract_string_vec(&matchstick_yaml, "fixtures");
+            // Fixture files with contract calls which are mocked in every test suite
+            config.mock_fixtures = parser::extract_string_vec(&matchstick_yaml, "mockFixtures");
+            // Fails every test which leaves references to missing entities in the store
+            config.strict_references = parser::extract_bool_or(
+                &matchstick_yaml,
+                "strictReferences",
+                config.strict_references,
+            );
+            // Fails the mocks of functions which are not declared in the manifest ABIs
+            // or whose signature doesn't match the ABI, instead of logging a warning
+            config.strict_mocks =
+                parser::extract_bool_or(&matchstick_yaml, "strictMocks", config.strict_mocks);
+            // The JSON-RPC node unmocked contract calls are recorded from with `--record-calls`,
+            // at the latest block unless `rpcBlock` is set
+            config.rpc_url = Some(parser::extract_string_or(
+                &matchstick_yaml,
+                "rpcUrl",
+                "".to_owned(),
+            ))
+            .filter(|url| !url.is_empty());
+            config.rpc_block = parser::extract_u64(&matchstick_yaml, "rpcBlock");
+            // The recorded calls are replayed from the cassette, which defaults to
+            // the `.cassette.json` file inside the tests folder.
+            config.cassette_path = parser::extract_string_or(
+                &matchstick_yaml,
+                "cassettePath",
+                format!("{}/.cassette.json", config.tests_path.trim_end_matches('/')),
+            );
+            // The data sources get the addresses of the network from the networks file,
+            // instead of the addresses in the manifest
+            config.network = Some(parser::extract_string_or(
+                &matchstick_yaml,
+                "network",
+                "".to_owned(),
+            ))
+            .filter(|network| !network.is_empty());
+            config.networks_path =
+                parser::extract_string_or(&matchstick_yaml, "networksPath", config.networks_path);
         }
 
         config
@@ -50,6 +117,15 @@ mod config_tests {
         assert_eq!(config.libs_path, "./node_modules".to_owned());
         assert_eq!(config.tests_path, "./tests".to_owned());
         assert_eq!(config.manifest_path, "./subgraph.yaml".to_owned());
+        assert!(config.fixtures.is_empty());
+        assert!(config.mock_fixtures.is_empty());
+        assert_eq!(config.artifacts_path, None);
+        assert!(!config.strict_references);
+        assert!(!config.strict_mocks);
+        assert_eq!(config.rpc_url, None);
+        assert_eq!(config.cassette_path, "./tests/.cassette.json".to_owned());
+        assert_eq!(config.network, None);
+        assert_eq!(config.networks_path, "./networks.json".to_owned());
     }
 
     #[test]
@@ -57,5 +133,20 @@ mod config_tests {
         let config = MatchstickConfig::from("mocks/yamls/matchstick.yaml");
 
         assert_eq!(config.tests_path, "./specs".to_owned());
+        assert_eq!(config.artifacts_path, Some("./specs/.artifacts".to_owned()));
+        assert!(config.strict_references);
+        assert!(config.strict_mocks);
+        assert_eq!(config.rpc_url, Some("http://127.0.0.1:8545".to_owned()));
+        assert_eq!(config.rpc_block, Some(15000000));
+        assert_eq!(config.cassette_path, "./specs/.cassette.json".to_owned());
+        assert_eq!(config.network, Some("goerli".to_owned()));
+        assert_eq!(
+            config.fixtures,
+            vec!["./tests/fixtures/store.yaml".to_owned()]
+        );
+        assert_eq!(
+            config.mock_fixtures,
+            vec!["./tests/fixtures/mocks.yaml".to_owned()]
+        );
     }
 }
@@ -0,0 +1,272 @@
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::anyhow;
+use graph::prelude::ethabi::{Contract, Function, ParamType, Token};
+
+use crate::context::assertions::{closest_ids, edit_distance};
+use crate::context::conversion::signature_types;
+use crate::logging;
+use crate::parser;
+use crate::STRICT_MOCKS;
+
+/// The contract ABIs declared under `mapping.abis` in the subgraph manifest,
+/// used to validate mocked functions and to derive their signatures.
+#[derive(Default)]
+pub(crate) struct ContractAbis {
+    contracts: Vec<(String, Contract)>,
+}
+
+impl ContractAbis {
+    /// Loads the ABIs declared in the manifest, resolving their files relative to it.
+    /// Without a manifest nothing is loaded and mocks are not validated.
+    pub(crate) fn load(manifest: &Path) -> ContractAbis {
+        let manifest_str = match manifest.to_str() {
+            Some(path) if manifest.is_file() => path,
+            _ => return ContractAbis::default(),
+        };
+        let root = manifest.parent().unwrap_or_else(|| Path::new(""));
+
+        let contracts = parser::collect_abis(manifest_str)
+            .into_iter()
+            .filter_map(|(name, file)| {
+                let path = root.join(&file);
+                let contract = File::open(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| Contract::load(file).map_err(anyhow::Error::from));
+
+                match contract {
+                    Ok(contract) => Some((name, contract)),
+                    Err(err) => {
+                        logging::warning!(
+                            "Couldn't load the ABI `{}` from `{}`, mocks of its functions won't be validated: {}",
+                            name,
+                            path.display(),
+                            err
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        ContractAbis { contracts }
+    }
+
+    /// The functions named `fn_name` in all ABIs, together with the name of their ABI.
+    fn functions(&self, fn_name: &str) -> Vec<(&String, &Function)> {
+        self.contracts
+            .iter()
+            .flat_map(|(name, contract)| contract.functions().map(move |f| (name, f)))
+            .filter(|(_, function)| function.name == fn_name)
+            .collect()
+    }
+
+    /// Validates the signature of a mocked function against the ABIs and returns it.
+    /// An empty signature is derived from the ABI function with the name, overloaded functions
+    /// are told apart by the number and the types of the arguments.
+    /// Functions which are not declared in any ABI and signatures which don't match the ABI
+    /// are only logged as warnings, unless `strictMocks` is set.
+    pub(crate) fn resolve_signature(
+        &self,
+        fn_name: &str,
+        fn_signature: &str,
+        fn_args: &[Token],
+    ) -> Result<String, anyhow::Error> {
+        let functions = self.functions(fn_name);
+
+        let strict = STRICT_MOCKS.with(|strict| *strict.borrow());
+
+        if functions.is_empty() {
+            if fn_signature.is_empty() || (strict && !self.contracts.is_empty()) {
+                return Err(self.unknown_function(fn_name));
+            }
+            if !self.contracts.is_empty() {
+                logging::warning!("{}", self.unknown_function(fn_name));
+            }
+            return Ok(fn_signature.to_owned());
+        }
+
+        if fn_signature.is_empty() {
+            return derive_signature(fn_name, &functions, fn_args);
+        }
+        if !fn_signature.starts_with(&format!("{}(", fn_name)) {
+            return Err(anyhow!(
+                "The signature `{}` should start with the function name `{}(`",
+                fn_signature,
+                fn_name
+            ));
+        }
+
+        let (inputs, outputs) = signature_types(fn_signature);
+        let declared = functions.iter().any(|(_, function)| {
+            let function_inputs: Vec<&ParamType> =
+                function.inputs.iter().map(|p| &p.kind).collect();
+            let function_outputs: Vec<&ParamType> =
+                function.outputs.iter().map(|p| &p.kind).collect();
+
+            inputs.iter().collect::<Vec<&ParamType>>() == function_inputs
+                && outputs.as_ref().map_or(true, |outputs| {
+                    outputs.iter().collect::<Vec<&ParamType>>() == function_outputs
+                })
+        });
+
+        if declared {
+            return Ok(fn_signature.to_owned());
+        }
+        if strict {
+            return Err(anyhow!(
+                "The signature `{}` doesn't match the ABI, the mock may be stale. Declared signatures:\n{}",
+                fn_signature,
+                format_functions(&functions)
+            ));
+        }
+
+        if let Some((abi, closest)) = functions
+            .iter()
+            .min_by_key(|(_, function)| edit_distance(&function.signature(), fn_signature))
+        {
+            logging::warning!(
+                "The signature `{}` doesn't match the ABI, the mock may be stale. Closest declared signature: {} ({})",
+                fn_signature,
+                closest.signature(),
+                abi
+            );
+        }
+        Ok(fn_signature.to_owned())
+    }
+
+    /// Derives the signature of a function from its name and the number of its arguments,
+    /// for mocks whose arguments can only be typed once the signature is known.
+    pub(crate) fn signature_by_arity(
+        &self,
+        fn_name: &str,
+        arity: usize,
+    ) -> Result<String, anyhow::Error> {
+        let functions = self.functions(fn_name);
+        if functions.is_empty() {
+            return Err(self.unknown_function(fn_name));
+        }
+
+        let mut candidates: Vec<String> = functions
+            .iter()
+            .filter(|(_, function)| function.inputs.len() == arity)
+            .map(|(_, function)| function.signature())
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        match candidates.len() {
+            1 => Ok(candidates.remove(0)),
+            0 => Err(anyhow!(
+                "None of the declared `{}` functions takes {} arguments. Declared signatures:\n{}",
+                fn_name,
+                arity,
+                format_functions(&functions)
+            )),
+            _ => Err(anyhow!(
+                "Function `{}` is overloaded with {} arguments, pass one of the signatures:\n{}",
+                fn_name,
+                arity,
+                candidates.join("\n")
+            )),
+        }
+    }
+
+    fn unknown_function(&self, fn_name: &str) -> anyhow::Error {
+        let abi_names: Vec<&str> = self
+            .contracts
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        let mut fn_names: Vec<String> = self
+            .contracts
+            .iter()
+            .flat_map(|(_, contract)| contract.functions().map(|f| f.name.clone()))
+            .collect();
+        fn_names.sort();
+        fn_names.dedup();
+
+        let closest: Vec<&str> = closest_ids(fn_names.iter(), fn_name)
+            .into_iter()
+            .map(|name| name.as_str())
+            .collect();
+
+        if abi_names.is_empty() {
+            anyhow!(
+                "Function `{}` is mocked without a signature, but no ABIs are declared in the manifest",
+                fn_name
+            )
+        } else if closest.is_empty() {
+            anyhow!(
+                "Function `{}` is not declared in the ABIs of the manifest ({})",
+                fn_name,
+                abi_names.join(", ")
+            )
+        } else {
+            anyhow!(
+                "Function `{}` is not declared in the ABIs of the manifest ({}). Did you mean: {}?",
+                fn_name,
+                abi_names.join(", "),
+                closest.join(", ")
+            )
+        }
+    }
+}
+
+/// Picks the function whose inputs match the mocked arguments and returns its signature.
+fn derive_signature(
+    fn_name: &str,
+    functions: &[(&String, &Function)],
+    fn_args: &[Token],
+) -> Result<String, anyhow::Error> {
+    let same_arity: Vec<&(&String, &Function)> = functions
+        .iter()
+        .filter(|(_, function)| function.inputs.len() == fn_args.len())
+        .collect();
+
+    let mut candidates: Vec<String> = if same_arity.len() > 1 {
+        same_arity
+            .iter()
+            .filter(|(_, function)| {
+                function
+                    .inputs
+                    .iter()
+                    .zip(fn_args.iter())
+                    .all(|(param, arg)| arg.type_check(&param.kind))
+            })
+            .map(|(_, function)| function.signature())
+            .collect()
+    } else {
+        same_arity
+            .iter()
+            .map(|(_, function)| function.signature())
+            .collect()
+    };
+    // The same function may be declared in several ABIs
+    candidates.sort();
+    candidates.dedup();
+
+    match candidates.len() {
+        1 => Ok(candidates.remove(0)),
+        0 => Err(anyhow!(
+            "None of the declared `{}` functions takes {} arguments of the mocked types. Declared signatures:\n{}",
+            fn_name,
+            fn_args.len(),
+            format_functions(functions)
+        )),
+        _ => Err(anyhow!(
+            "Function `{}` is overloaded and the arguments match several signatures, pass one of them:\n{}",
+            fn_name,
+            candidates.join("\n")
+        )),
+    }
+}
+
+fn format_functions(functions: &[(&String, &Function)]) -> String {
+    functions
+        .iter()
+        .map(|(abi, function)| format!("{} ({})", function.signature(), abi))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
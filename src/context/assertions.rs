@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use graph::data::store::Value;
+use graph::prelude::{BigDecimal, BigInt};
+use graph_graphql::graphql_parser::schema;
+
+use crate::context::conversion::format_typed_value;
+use crate::context::entity_id::IdType;
+use crate::context::get_entity_definition;
+use crate::logging;
+
+/// Compares the expected fields of an entity with the stored ones and returns a line
+/// for every missing, extra and differing field, sorted by field name.
+/// Stored fields for which `is_ignored` returns true are only compared when they are expected.
+pub(crate) fn entity_diff(
+    expected: &HashMap<String, Value>,
+    actual: &HashMap<String, Value>,
+    is_ignored: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let fields: BTreeSet<&String> = expected
+        .keys()
+        .chain(actual.keys().filter(|field| !is_ignored(field)))
+        .collect();
+
+    fields
+        .into_iter()
+        .filter_map(|field| match (expected.get(field), actual.get(field)) {
+            // Unset fields are the same as null fields
+            (Some(Value::Null), None) | (None, Some(Value::Null)) => None,
+            (Some(expected), Some(actual)) if !values_equal(expected, actual) => Some(format!(
+                "~ '{}': expected {} but was {}",
+                field,
+                format_typed_value(expected),
+                format_typed_value(actual)
+            )),
+            (Some(expected), None) => Some(format!(
+                "- '{}': expected {} but the field is missing",
+                field,
+                format_typed_value(expected)
+            )),
+            (None, Some(actual)) => Some(format!(
+                "+ '{}': unexpected field with value {}",
+                field,
+                format_typed_value(actual)
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses the expected value of `assert.fieldEquals` according to the schema type of the field.
+/// Lists are expected in the format of `Value`'s Display impl, e.g. `[0x01, 0x02]`.
+/// Returns None if the string can't be parsed as a value of that type.
+pub(crate) fn parse_expected_value(
+    expected: &str,
+    field_type: &schema::Type<'static, String>,
+) -> Option<Value> {
+    match field_type {
+        schema::Type::NonNullType(inner) => parse_expected_value(expected, inner),
+        _ if expected == "null" => Some(Value::Null),
+        schema::Type::ListType(inner) => {
+            let elements = expected.trim().strip_prefix('[')?.strip_suffix(']')?;
+            split_list(elements)
+                .into_iter()
+                .map(|element| parse_expected_value(element.trim(), inner))
+                .collect::<Option<Vec<Value>>>()
+                .map(Value::List)
+        }
+        schema::Type::NamedType(type_name) => match type_name.as_str() {
+            "Int" => expected.parse::<i32>().ok().map(Value::Int),
+            "Int8" => IdType::Int8.parse(expected).ok(),
+            "BigInt" => BigInt::from_str(expected).ok().map(Value::BigInt),
+            "BigDecimal" => BigDecimal::from_str(expected).ok().map(Value::BigDecimal),
+            "Boolean" => expected.parse::<bool>().ok().map(Value::Bool),
+            "Bytes" => IdType::Bytes.parse(expected).ok(),
+            type_name if get_entity_definition(type_name).is_some() => {
+                IdType::of(type_name).parse(expected).ok()
+            }
+            _ => Some(Value::String(expected.to_owned())),
+        },
+    }
+}
+
+/// Splits the elements of a list on the commas which are not inside a nested list.
+fn split_list(elements: &str) -> Vec<&str> {
+    if elements.trim().is_empty() {
+        return vec![];
+    }
+
+    let mut depth = 0;
+    let mut start = 0;
+    let mut parts = vec![];
+    for (i, c) in elements.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&elements[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&elements[start..]);
+    parts
+}
+
+/// Compares two values by their meaning rather than their representation.
+/// `Int` and `BigInt` values are compared as numbers, lists are compared element by element.
+/// BigDecimals are compared numerically and Bytes by their content, so `1.0` equals `1`
+/// and hex strings are case-insensitive once parsed.
+pub(crate) fn values_equal(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Int(a), Value::BigInt(b)) | (Value::BigInt(b), Value::Int(a)) => {
+            &BigInt::from(*a) == b
+        }
+        (Value::List(expected), Value::List(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual.iter())
+                    .all(|(expected, actual)| values_equal(expected, actual))
+        }
+        (expected, actual) => expected == actual,
+    }
+}
+
+/// Maximum number of near-matching entities listed when a store assertion fails.
+const NEAR_MATCHES_LIMIT: usize = 3;
+
+/// Returns the ids closest to `id` by edit distance, to help spot typos in failed assertions.
+pub(crate) fn closest_ids<'a>(ids: impl Iterator<Item = &'a String>, id: &str) -> Vec<&'a String> {
+    let mut ids: Vec<(usize, &String)> =
+        ids.map(|other| (edit_distance(other, id), other)).collect();
+    ids.sort();
+    ids.into_iter()
+        .take(NEAR_MATCHES_LIMIT)
+        .map(|(_, id)| id)
+        .collect()
+}
+
+/// Finds the entities which match the most of the expected fields, but not all of them,
+/// together with the differences of each entity from the expected fields.
+pub(crate) fn near_matches<'a>(
+    entities: &'a HashMap<String, HashMap<String, Value>>,
+    expected: &HashMap<String, Value>,
+) -> Vec<(&'a String, Vec<String>)> {
+    let mut matches: Vec<(usize, &String, Vec<String>)> = entities
+        .iter()
+        .map(|(id, entity)| {
+            let fields = expected
+                .keys()
+                .filter_map(|field| {
+                    entity
+                        .get(field)
+                        .map(|value| (field.clone(), value.clone()))
+                })
+                .collect();
+            let diff = entity_diff(expected, &fields, |_| false);
+            (diff.len(), id, diff)
+        })
+        .filter(|(mismatches, _, _)| *mismatches < expected.len())
+        .collect();
+
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(b.1)));
+    matches
+        .into_iter()
+        .take(NEAR_MATCHES_LIMIT)
+        .map(|(_, id, diff)| (id, diff))
+        .collect()
+}
+
+/// Checks whether the entity has all the expected field values.
+pub(crate) fn matches_fields(
+    entity: &HashMap<String, Value>,
+    expected: &HashMap<String, Value>,
+) -> bool {
+    expected
+        .iter()
+        .all(|(field, expected)| match entity.get(field) {
+            Some(actual) => values_equal(expected, actual),
+            None => matches!(expected, Value::Null),
+        })
+}
+
+/// Levenshtein distance between two strings.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let current = distances[j + 1];
+            distances[j + 1] = if a_char == *b_char {
+                previous
+            } else {
+                1 + previous.min(current).min(distances[j])
+            };
+            previous = current;
+        }
+    }
+
+    distances[b.len()]
+}
+
+/// A message and call site registered with `_assert.withMessage`,
+/// which is added to the failure of the next assertion.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AssertionMessage {
+    pub(crate) message: String,
+    pub(crate) call_site: String,
+}
+
+/// Logs the failure of an assertion, followed by the user message and call site if there are any.
+pub(crate) fn log_failure(message: &Option<AssertionMessage>, failure: String) {
+    let mut lines = vec![];
+    if let Some(AssertionMessage { message, .. }) = message {
+        if !message.is_empty() {
+            lines.push(message.clone());
+        }
+    }
+    lines.push(failure);
+    if let Some(AssertionMessage { call_site, .. }) = message {
+        if !call_site.is_empty() {
+            lines.push(format!("at {}", call_site));
+        }
+    }
+
+    logging::error!("{}", lines.join("\n"));
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use graph::prelude::ethabi::{Address, ParamType, Token};
+
+use crate::context::conversion::get_kind;
+use crate::context::mock_result::MockResult;
+
+/// Matches a single argument of a mocked contract call.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ArgMatcher {
+    /// The argument has to be equal to the value
+    Exact(Token),
+    /// Any argument matches
+    Any,
+    /// Any argument of the ABI type matches, e.g. `type:address`
+    OfType(ParamType),
+    /// The argument has to be equal to one of the values
+    OneOf(Vec<Token>),
+}
+
+impl ArgMatcher {
+    /// Builds a matcher from its name and the value passed at the position of the argument.
+    /// The value is only used by the `eq` (or empty) and `oneOf` matchers, `oneOf` expects
+    /// an array of candidate values, which have to match the type of the argument.
+    pub(crate) fn parse(
+        matcher: &str,
+        value: Token,
+        param_type: &ParamType,
+    ) -> Result<ArgMatcher, anyhow::Error> {
+        let check_type = |value: &Token| {
+            if value.type_check(param_type) {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Expected a value of type {:?}, but received {:?}",
+                    param_type,
+                    value
+                ))
+            }
+        };
+
+        match matcher.trim() {
+            "" | "eq" => {
+                check_type(&value)?;
+                Ok(ArgMatcher::Exact(value))
+            }
+            "any" => Ok(ArgMatcher::Any),
+            "oneOf" => match value {
+                Token::Array(values) | Token::FixedArray(values) => {
+                    values.iter().try_for_each(check_type)?;
+                    Ok(ArgMatcher::OneOf(values))
+                }
+                value => Err(anyhow!(
+                    "The `oneOf` matcher expects an array of values, but received {:?}",
+                    value
+                )),
+            },
+            matcher => match matcher.strip_prefix("type:") {
+                Some(kind) => Ok(ArgMatcher::OfType(get_kind(kind.to_owned()))),
+                None => Err(anyhow!(
+                    "Unknown argument matcher `{}`, expected one of: eq, any, oneOf, type:<abi type>",
+                    matcher
+                )),
+            },
+        }
+    }
+
+    pub(crate) fn matches(&self, arg: &Token) -> bool {
+        match self {
+            ArgMatcher::Exact(value) => value == arg,
+            ArgMatcher::Any => true,
+            ArgMatcher::OfType(param_type) => arg.type_check(param_type),
+            ArgMatcher::OneOf(values) => values.contains(arg),
+        }
+    }
+
+    fn is_wildcard(&self) -> bool {
+        !matches!(self, ArgMatcher::Exact(_))
+    }
+}
+
+impl fmt::Display for ArgMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgMatcher::Exact(value) => write!(f, "{:?}", value),
+            ArgMatcher::Any => write!(f, "any"),
+            ArgMatcher::OfType(param_type) => write!(f, "type:{}", param_type),
+            ArgMatcher::OneOf(values) => write!(f, "oneOf{:?}", values),
+        }
+    }
+}
+
+/// A contract call mocked with argument matchers instead of exact arguments.
+#[derive(Clone, Debug)]
+pub(crate) struct MatcherMock {
+    /// The address of the mocked contract, `None` matches calls to any contract
+    pub(crate) contract_address: Option<Address>,
+    pub(crate) fn_name: String,
+    pub(crate) fn_signature: String,
+    pub(crate) matchers: Vec<ArgMatcher>,
+    pub(crate) result: MockResult,
+    /// Number of calls answered by the mock
+    pub(crate) hits: usize,
+}
+
+impl MatcherMock {
+    fn matches(
+        &self,
+        contract_address: &Address,
+        fn_name: &str,
+        fn_signature: &str,
+        args: &[Token],
+    ) -> bool {
+        self.contract_address
+            .as_ref()
+            .map_or(true, |address| address == contract_address)
+            && self.fn_name == fn_name
+            && self.fn_signature == fn_signature
+            && self.matchers.len() == args.len()
+            && self
+                .matchers
+                .iter()
+                .zip(args.iter())
+                .all(|(matcher, arg)| matcher.matches(arg))
+    }
+
+    /// Number of wildcards of the mock, the contract address counts as one if it's not set.
+    fn wildcards(&self) -> usize {
+        self.matchers.iter().filter(|m| m.is_wildcard()).count()
+            + usize::from(self.contract_address.is_none())
+    }
+}
+
+impl fmt::Display for MatcherMock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on ", self.fn_signature)?;
+        match &self.contract_address {
+            Some(address) => write!(f, "{:?}", address)?,
+            None => write!(f, "any contract")?,
+        }
+        let matchers: Vec<String> = self.matchers.iter().map(|m| m.to_string()).collect();
+        write!(f, " with [{}]", matchers.join(", "))
+    }
+}
+
+/// Finds the index of the matcher mock for a contract call. When several mocks match the call,
+/// the one with the fewest wildcards wins, and from those the one mocked last.
+/// Mocks with exact arguments are looked up before and always take precedence.
+pub(crate) fn find_matcher_mock(
+    mocks: &[MatcherMock],
+    contract_address: &Address,
+    fn_name: &str,
+    fn_signature: &str,
+    args: &[Token],
+) -> Option<usize> {
+    mocks
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, mock)| mock.matches(contract_address, fn_name, fn_signature, args))
+        .min_by_key(|(_, mock)| mock.wildcards())
+        .map(|(index, _)| index)
+}
@@ -0,0 +1,281 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use graph::data::store::scalar::Bytes;
+use graph::prelude::ethabi::{self, Address, ParamType};
+
+use crate::context::contract_calls::ContractCall;
+use crate::context::conversion::signature_types;
+use crate::context::mock_result::{MockResult, RevertReason};
+
+/// Selector of the `Error(string)` error, which `revert("reason")` and `require` revert with.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// The JSON-RPC error code of `eth_call`s which reverted.
+const EXECUTION_REVERTED: i64 = 3;
+
+/// How long to wait for the node to answer a call.
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The outcome of an `eth_call`, with the raw ABI-encoded return or revert data.
+#[derive(Clone, Debug, PartialEq)]
+enum CallOutcome {
+    Return(Bytes),
+    Revert(Bytes),
+}
+
+/// A contract call recorded from a JSON-RPC node.
+#[derive(Clone, Debug, PartialEq)]
+struct CassetteEntry {
+    contract_address: Address,
+    fn_signature: String,
+    /// The ABI-encoded call data, with the function selector
+    call_data: Bytes,
+    /// The block the call was made at, `None` for the latest block
+    block: Option<u64>,
+    outcome: CallOutcome,
+}
+
+impl CassetteEntry {
+    fn from_json(entry: &serde_json::Value) -> Result<CassetteEntry, anyhow::Error> {
+        let string = |key: &str| {
+            entry
+                .get(key)
+                .and_then(|value| value.as_str())
+                .ok_or_else(|| anyhow!("Expected `{}` in cassette entry {}", key, entry))
+        };
+        let bytes = |value: &str| {
+            Bytes::from_str(&value.to_ascii_lowercase())
+                .map_err(|err| anyhow!("Invalid hex data `{}`: {}", value, err))
+        };
+
+        let outcome = match (entry.get("return"), entry.get("revert")) {
+            (Some(_), _) => CallOutcome::Return(bytes(string("return")?)?),
+            (None, Some(_)) => CallOutcome::Revert(bytes(string("revert")?)?),
+            (None, None) => {
+                return Err(anyhow!(
+                    "Expected `return` or `revert` in cassette entry {}",
+                    entry
+                ))
+            }
+        };
+
+        Ok(CassetteEntry {
+            contract_address: Address::from_str(string("address")?)
+                .map_err(|err| anyhow!("Invalid address in cassette entry {}: {}", entry, err))?,
+            fn_signature: string("signature")?.to_owned(),
+            call_data: bytes(string("data")?)?,
+            block: entry.get("block").and_then(|block| block.as_u64()),
+            outcome,
+        })
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut entry = serde_json::json!({
+            "address": format!("{:?}", self.contract_address),
+            "signature": self.fn_signature,
+            "data": self.call_data.to_string(),
+            "block": self.block,
+        });
+        let (key, data) = match &self.outcome {
+            CallOutcome::Return(data) => ("return", data),
+            CallOutcome::Revert(data) => ("revert", data),
+        };
+        entry[key] = serde_json::Value::from(data.to_string());
+        entry
+    }
+
+    fn is_call(&self, contract_address: &Address, fn_signature: &str, call_data: &Bytes) -> bool {
+        self.contract_address == *contract_address
+            && self.fn_signature == fn_signature
+            && self.call_data == *call_data
+    }
+}
+
+/// Contract calls recorded from a JSON-RPC node, which answers the calls that are not mocked.
+/// In record mode the calls missing from the cassette are forwarded to the node and saved,
+/// otherwise the cassette is replayed offline.
+#[derive(Clone, Debug)]
+pub(crate) struct Cassette {
+    path: PathBuf,
+    /// The JSON-RPC endpoint of the node in record mode, e.g. a local anvil or hardhat node
+    rpc_url: Option<String>,
+    /// The block the calls are made at, `None` for the latest block
+    block: Option<u64>,
+    entries: Vec<CassetteEntry>,
+}
+
+impl Cassette {
+    /// Loads the cassette, a missing cassette file has no recorded calls.
+    pub(crate) fn load(
+        path: &Path,
+        rpc_url: Option<String>,
+        block: Option<u64>,
+    ) -> Result<Cassette, anyhow::Error> {
+        Ok(Cassette {
+            path: path.to_path_buf(),
+            rpc_url,
+            block,
+            entries: read_entries(path)?,
+        })
+    }
+
+    /// Answers a contract call from the cassette, or from the node in record mode.
+    /// Returns `None` if the call was not recorded and the cassette is replayed.
+    pub(crate) fn call(
+        &mut self,
+        call: &ContractCall,
+    ) -> Result<Option<MockResult>, anyhow::Error> {
+        let (inputs, outputs) = signature_types(&call.fn_signature);
+        let mut call_data = ethabi::short_signature(&call.fn_name, &inputs).to_vec();
+        call_data.extend(ethabi::encode(&call.args));
+        let call_data = Bytes::from(call_data.as_slice());
+
+        let recorded = self
+            .entries
+            .iter()
+            .find(|entry| {
+                entry.block == self.block
+                    && entry.is_call(&call.contract_address, &call.fn_signature, &call_data)
+            })
+            .map(|entry| entry.outcome.clone());
+        let outcome = match (recorded, &self.rpc_url) {
+            (Some(outcome), _) => outcome,
+            (None, Some(rpc_url)) => {
+                let outcome = eth_call(rpc_url, &call.contract_address, &call_data, self.block)
+                    .with_context(|| format!("Could not record the call to {}", call))?;
+                self.entries.push(CassetteEntry {
+                    contract_address: call.contract_address,
+                    fn_signature: call.fn_signature.clone(),
+                    call_data,
+                    block: self.block,
+                    outcome: outcome.clone(),
+                });
+                self.save()?;
+                outcome
+            }
+            (None, None) => return Ok(None),
+        };
+
+        match outcome {
+            CallOutcome::Return(data) => {
+                let tokens = ethabi::decode(&outputs.unwrap_or_default(), data.as_slice())
+                    .map_err(|err| {
+                        anyhow!(
+                            "Could not decode the recorded return data of {}: {}",
+                            call,
+                            err
+                        )
+                    })?;
+                Ok(Some(MockResult::Return(tokens)))
+            }
+            CallOutcome::Revert(data) => Ok(Some(MockResult::Revert(revert_reason(&data)))),
+        }
+    }
+
+    /// Writes the cassette, keeping the calls recorded by other test suites in the meantime.
+    fn save(&self) -> Result<(), anyhow::Error> {
+        let mut entries = read_entries(&self.path)?;
+        for entry in self.entries.iter() {
+            if !entries.contains(entry) {
+                entries.push(entry.clone());
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json: Vec<serde_json::Value> = entries.iter().map(|e| e.to_json()).collect();
+        std::fs::write(&self.path, serde_json::to_string_pretty(&json)?)
+            .with_context(|| format!("Could not write cassette `{}`", self.path.display()))
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<CassetteEntry>, anyhow::Error> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read cassette `{}`", path.display()))?;
+    let json: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("Could not parse cassette `{}`", path.display()))?;
+
+    json.as_array()
+        .ok_or_else(|| anyhow!("Expected a list of calls in cassette `{}`", path.display()))?
+        .iter()
+        .map(CassetteEntry::from_json)
+        .collect()
+}
+
+/// Decodes why a call reverted from the revert data.
+fn revert_reason(data: &Bytes) -> RevertReason {
+    let data = data.as_slice();
+    match data {
+        [] => RevertReason::Unknown,
+        data if data.starts_with(&ERROR_SELECTOR) => {
+            match ethabi::decode(&[ParamType::String], &data[4..]) {
+                Ok(tokens) => match tokens.into_iter().next() {
+                    Some(ethabi::Token::String(reason)) => RevertReason::Message(reason),
+                    _ => RevertReason::CustomError(Bytes::from(data)),
+                },
+                Err(_) => RevertReason::CustomError(Bytes::from(data)),
+            }
+        }
+        data if data.len() >= 4 => RevertReason::CustomError(Bytes::from(data)),
+        _ => RevertReason::Unknown,
+    }
+}
+
+/// Makes an `eth_call` to the node, calls which revert are not an error. A call reverted if
+/// the node answers with the `execution reverted` error code or with the revert data.
+fn eth_call(
+    rpc_url: &str,
+    contract_address: &Address,
+    call_data: &Bytes,
+    block: Option<u64>,
+) -> Result<CallOutcome, anyhow::Error> {
+    let block = match block {
+        Some(block) => format!("0x{:x}", block),
+        None => "latest".to_owned(),
+    };
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_call",
+        "params": [
+            { "to": format!("{:?}", contract_address), "data": call_data.to_string() },
+            block,
+        ],
+    });
+
+    let response: serde_json::Value = reqwest::blocking::Client::builder()
+        .timeout(RPC_TIMEOUT)
+        .build()?
+        .post(rpc_url)
+        .json(&request)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Could not call the node at `{}`", rpc_url))?
+        .json()
+        .context("Could not parse the JSON-RPC response")?;
+    let bytes = |value: Option<&serde_json::Value>| {
+        let hex = value.and_then(|value| value.as_str()).unwrap_or("0x");
+        Bytes::from_str(hex).map_err(|err| anyhow!("Invalid hex data `{}`: {}", hex, err))
+    };
+
+    match response.get("error") {
+        Some(error) => {
+            let code = error.get("code").and_then(|code| code.as_i64());
+            let data = error.get("data").filter(|data| !data.is_null());
+            if code == Some(EXECUTION_REVERTED) || data.is_some() {
+                Ok(CallOutcome::Revert(bytes(data)?))
+            } else {
+                Err(anyhow!("eth_call failed: {}", error))
+            }
+        }
+        None => Ok(CallOutcome::Return(bytes(response.get("result"))?)),
+    }
+}
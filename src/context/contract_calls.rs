@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use graph::prelude::ethabi::{Address, Token};
+
+use crate::context::mock_result::RevertReason;
+
+/// A call of a smart contract function made with `ethereum.call`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ContractCall {
+    pub(crate) contract_address: Address,
+    pub(crate) fn_name: String,
+    pub(crate) fn_signature: String,
+    pub(crate) args: Vec<Token>,
+}
+
+impl ContractCall {
+    /// Checks whether the call was made to the contract (any contract if `None`)
+    /// with the function signature and, if passed, with the arguments.
+    pub(crate) fn matches(
+        &self,
+        contract_address: Option<&Address>,
+        fn_signature: &str,
+        args: Option<&[Token]>,
+    ) -> bool {
+        contract_address.map_or(true, |address| *address == self.contract_address)
+            && self.fn_signature == fn_signature
+            && args.map_or(true, |args| self.args == args)
+    }
+}
+
+impl fmt::Display for ContractCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} on {:?} with {:?}",
+            self.fn_signature, self.contract_address, self.args
+        )
+    }
+}
+
+/// A contract call recorded by `ethereum.call`, `mocked` is false if no mock matched it.
+#[derive(Clone, Debug)]
+pub(crate) struct RecordedCall {
+    pub(crate) call: ContractCall,
+    pub(crate) mocked: bool,
+    /// Why the call reverted, if the mock reverts
+    pub(crate) revert: Option<RevertReason>,
+}
+
+impl fmt::Display for RecordedCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.call)?;
+        if !self.mocked {
+            write!(f, " (not mocked)")?;
+        }
+        if let Some(reason) = &self.revert {
+            write!(f, " reverted {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// A function mocked with `mockFunction` and the number of calls it answered.
+#[derive(Clone, Debug)]
+pub(crate) struct ExactMock {
+    pub(crate) call: ContractCall,
+    pub(crate) hits: usize,
+}
+
+/// Parses the contract address of a mock or an assertion,
+/// an empty address or `*` stands for any contract.
+pub(crate) fn parse_contract_address(address: &str) -> Result<Option<Address>, anyhow::Error> {
+    match address.trim() {
+        "" | "*" => Ok(None),
+        address => Address::from_str(address)
+            .map(Some)
+            .map_err(|err| anyhow!("Invalid contract address `{}`: {}", address, err)),
+    }
+}
+
+/// Formats the recorded calls as a numbered list, one call per line.
+pub(crate) fn format_calls(calls: &[RecordedCall]) -> String {
+    if calls.is_empty() {
+        return "No contract calls were recorded.".to_owned();
+    }
+
+    calls
+        .iter()
+        .enumerate()
+        .map(|(i, call)| format!("{}. {}", i + 1, call))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
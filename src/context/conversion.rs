@@ -1,14 +1,19 @@
 use regex::Regex;
 use std::boxed::Box;
+use std::str::FromStr;
 
+use anyhow::anyhow;
 use graph::{
     data::store::{scalar::Bytes, Value},
     prelude::{
-        ethabi::{ParamType, Token},
-        BigInt,
+        ethabi::{Address, ParamType, Token, Uint},
+        BigDecimal, BigInt,
     },
 };
+use graph_graphql::graphql_parser::schema;
 
+use crate::context::entity_id::IdType;
+use crate::context::{get_entity_definition, get_enum_values};
 use crate::logging;
 
 /// Converts string argument types from the function signature into ethabi::ParamType.
@@ -80,6 +85,185 @@ pub(crate) fn get_token_value(token: Token) -> Value {
     }
 }
 
+/// Formats graph::data::store::Value together with its type, e.g. `BigInt(100)` or `String("abc")`,
+/// so values which look the same as strings can be told apart in assertion errors.
+pub(crate) fn format_typed_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => format!("String({:?})", string),
+        Value::Int(int) => format!("Int({})", int),
+        Value::BigDecimal(decimal) => format!("BigDecimal({})", decimal),
+        Value::Bool(bool) => format!("Bool({})", bool),
+        Value::List(values) => format!(
+            "List([{}])",
+            values
+                .iter()
+                .map(format_typed_value)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Value::Null => "Null".to_owned(),
+        Value::Bytes(bytes) => format!("Bytes({})", bytes),
+        Value::BigInt(int) => format!("BigInt({})", int),
+    }
+}
+
+/// Converts graph::data::store::Value into JSON, keeping the type of the value.
+/// Every value is an object with the type as its only key, e.g. `{"BigInt": "100"}`,
+/// numbers that may not fit in a JSON number are written as strings.
+pub(crate) fn get_json_value(value: &Value) -> serde_json::Value {
+    let (kind, value) = match value {
+        Value::String(string) => ("String", serde_json::Value::from(string.clone())),
+        Value::Int(int) => ("Int", serde_json::Value::from(*int)),
+        Value::BigDecimal(decimal) => ("BigDecimal", serde_json::Value::from(decimal.to_string())),
+        Value::Bool(bool) => ("Bool", serde_json::Value::from(*bool)),
+        Value::List(values) => (
+            "List",
+            serde_json::Value::Array(values.iter().map(get_json_value).collect()),
+        ),
+        Value::Null => ("Null", serde_json::Value::Null),
+        Value::Bytes(bytes) => ("Bytes", serde_json::Value::from(bytes.to_string())),
+        Value::BigInt(int) => ("BigInt", serde_json::Value::from(int.to_string())),
+    };
+
+    let mut typed = serde_json::Map::new();
+    typed.insert(kind.to_owned(), value);
+    serde_json::Value::Object(typed)
+}
+
+/// Converts a value from a fixture file into graph::data::store::Value
+/// according to the schema type of the field it is assigned to.
+/// BigInt, BigDecimal and Int8 values can be written either as numbers or as strings,
+/// strings should be preferred for values that don't fit in 64 bits.
+pub(crate) fn get_fixture_value(
+    value: &serde_json::Value,
+    field_type: &schema::Type<'static, String>,
+) -> Result<Value, anyhow::Error> {
+    match (field_type, value) {
+        (_, serde_json::Value::Null) => Ok(Value::Null),
+        (schema::Type::NonNullType(inner), value) => get_fixture_value(value, inner),
+        (schema::Type::ListType(inner), serde_json::Value::Array(values)) => values
+            .iter()
+            .map(|value| get_fixture_value(value, inner))
+            .collect::<Result<Vec<Value>, _>>()
+            .map(Value::List),
+        (schema::Type::ListType(_), value) => Err(anyhow!("Expected a list, got `{}`", value)),
+        (schema::Type::NamedType(type_name), value) => get_fixture_scalar(value, type_name),
+    }
+}
+
+fn get_fixture_scalar(value: &serde_json::Value, type_name: &str) -> Result<Value, anyhow::Error> {
+    let invalid = || anyhow!("Expected a value of type `{}`, got `{}`", type_name, value);
+    let scalar = match value {
+        serde_json::Value::String(string) => string.clone(),
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::Bool(bool) => bool.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    match type_name {
+        "ID" | "String" if value.is_string() => Ok(Value::String(scalar)),
+        "Boolean" => value.as_bool().map(Value::Bool).ok_or_else(invalid),
+        "Int" => scalar.parse::<i32>().map(Value::Int).map_err(|_| invalid()),
+        "Int8" => IdType::Int8.parse(&scalar),
+        "BigInt" => BigInt::from_str(&scalar)
+            .map(Value::BigInt)
+            .map_err(|_| invalid()),
+        "BigDecimal" => BigDecimal::from_str(&scalar)
+            .map(Value::BigDecimal)
+            .map_err(|_| invalid()),
+        "Bytes" if value.is_string() => IdType::Bytes.parse(&scalar),
+        type_name if get_enum_values(type_name).is_some() => {
+            let values = get_enum_values(type_name).unwrap();
+            if value.is_string() && values.iter().any(|v| *v == scalar) {
+                Ok(Value::String(scalar))
+            } else {
+                Err(anyhow!(
+                    "`{}` is not a value of enum `{}`, expected one of: {}",
+                    value,
+                    type_name,
+                    values.join(", ")
+                ))
+            }
+        }
+        // References to other entities are stored as the id of the referenced entity
+        type_name if get_entity_definition(type_name).is_some() => {
+            IdType::of(type_name).parse(&scalar)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Converts a value from a mock fixture file into ethabi::Token according to its ABI type.
+/// Integers can be written either as numbers or as decimal strings, addresses and bytes
+/// as hex strings, and arrays and tuples as lists of values.
+pub(crate) fn get_fixture_token(
+    value: &serde_json::Value,
+    param_type: &ParamType,
+) -> Result<Token, anyhow::Error> {
+    let invalid = || anyhow!("Expected a value of type `{}`, got `{}`", param_type, value);
+    let string = || value.as_str().ok_or_else(invalid);
+    let values = |len: Option<usize>| {
+        value
+            .as_array()
+            .filter(|values| len.map_or(true, |len| values.len() == len))
+            .ok_or_else(invalid)
+    };
+
+    match param_type {
+        ParamType::Address => Address::from_str(string()?)
+            .map(Token::Address)
+            .map_err(|_| invalid()),
+        ParamType::Bool => value.as_bool().map(Token::Bool).ok_or_else(invalid),
+        ParamType::String => string().map(|string| Token::String(string.to_owned())),
+        ParamType::Bytes => Bytes::from_str(&string()?.to_ascii_lowercase())
+            .map(|bytes| Token::Bytes(bytes.as_slice().to_vec()))
+            .map_err(|_| invalid()),
+        ParamType::FixedBytes(size) => Bytes::from_str(&string()?.to_ascii_lowercase())
+            .ok()
+            .filter(|bytes| bytes.as_slice().len() == *size)
+            .map(|bytes| Token::FixedBytes(bytes.as_slice().to_vec()))
+            .ok_or_else(invalid),
+        ParamType::Int(_) | ParamType::Uint(_) => {
+            let number = match value {
+                serde_json::Value::Number(number) => number.to_string(),
+                serde_json::Value::String(string) => string.trim().to_owned(),
+                _ => return Err(invalid()),
+            };
+            let (negative, digits) = match number.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, number.as_str()),
+            };
+            let uint = Uint::from_dec_str(digits).map_err(|_| invalid())?;
+
+            match param_type {
+                // Negative numbers are stored in two's complement, like ethabi does it
+                ParamType::Int(_) if negative => {
+                    Ok(Token::Int((!uint).overflowing_add(Uint::one()).0))
+                }
+                ParamType::Int(_) => Ok(Token::Int(uint)),
+                _ if negative => Err(invalid()),
+                _ => Ok(Token::Uint(uint)),
+            }
+        }
+        ParamType::Array(inner) => values(None)?
+            .iter()
+            .map(|value| get_fixture_token(value, inner))
+            .collect::<Result<Vec<Token>, _>>()
+            .map(Token::Array),
+        ParamType::FixedArray(inner, size) => values(Some(*size))?
+            .iter()
+            .map(|value| get_fixture_token(value, inner))
+            .collect::<Result<Vec<Token>, _>>()
+            .map(Token::FixedArray),
+        ParamType::Tuple(types) => values(Some(types.len()))?
+            .iter()
+            .zip(types.iter())
+            .map(|(value, param_type)| get_fixture_token(value, param_type))
+            .collect::<Result<Vec<Token>, _>>()
+            .map(Token::Tuple),
+    }
+}
+
 /// Collects the arguments types from the function signature and returns a Vec
 /// Because the arguments could be tuples, it's not possible jus to split on every comma
 /// so we count the open parentheses and only split when there are none currently open.
@@ -112,3 +296,30 @@ pub(crate) fn collect_types(arg_str: &str) -> Vec<String> {
 
     arg_types
 }
+
+/// Parses the input and output types of a signature, e.g. "fnName(uint256):(string,bool)".
+/// The outputs are `None` if the signature doesn't declare them.
+pub(crate) fn signature_types(fn_signature: &str) -> (Vec<ParamType>, Option<Vec<ParamType>>) {
+    let start = fn_signature.find('(').map_or(0, |index| index + 1);
+    let (inputs, outputs) = match fn_signature[start..].split_once("):") {
+        Some((inputs, outputs)) => (inputs, Some(outputs)),
+        None => (fn_signature[start..].trim_end_matches(')'), None),
+    };
+
+    let parse = |types: &str| -> Vec<ParamType> {
+        collect_types(types).into_iter().map(get_kind).collect()
+    };
+
+    (
+        parse(inputs),
+        outputs.map(|outputs| {
+            let outputs = outputs.trim();
+            parse(
+                outputs
+                    .strip_prefix('(')
+                    .and_then(|o| o.strip_suffix(')'))
+                    .unwrap_or(outputs),
+            )
+        }),
+    )
+}
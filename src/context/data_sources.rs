@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use graph::data::store::Value;
+use graph::prelude::ethabi::Address;
+
+use crate::logging;
+use crate::parser::{self, ManifestDataSource};
+
+/// A data source created from a template with `dataSource.create` or `dataSource.createWithContext`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CreatedDataSource {
+    pub(crate) template: String,
+    pub(crate) params: Vec<String>,
+    /// The context of the data source, `None` if it was created without one
+    pub(crate) context: Option<BTreeMap<String, Value>>,
+}
+
+impl CreatedDataSource {
+    /// The address of the data source, the first param of ethereum templates.
+    pub(crate) fn address(&self) -> Option<Address> {
+        self.params
+            .first()
+            .and_then(|param| Address::from_str(param).ok())
+    }
+
+    /// Checks whether the data source was created from the template with the params and,
+    /// if passed, with the context.
+    pub(crate) fn matches(
+        &self,
+        template: &str,
+        params: &[String],
+        context: Option<&BTreeMap<String, Value>>,
+    ) -> bool {
+        self.template == template
+            && self.params == params
+            && context.map_or(true, |context| {
+                self.context
+                    .as_ref()
+                    .map_or(context.is_empty(), |c| c == context)
+            })
+    }
+}
+
+impl fmt::Display for CreatedDataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} with params {:?}", self.template, self.params)?;
+        if let Some(context) = &self.context {
+            let fields: Vec<String> = context
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .collect();
+            write!(f, " and context {{{}}}", fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the created data sources as a numbered list, one data source per line.
+pub(crate) fn format_data_sources(data_sources: &[CreatedDataSource]) -> String {
+    if data_sources.is_empty() {
+        return "No data sources were created.".to_owned();
+    }
+
+    data_sources
+        .iter()
+        .enumerate()
+        .map(|(i, data_source)| format!("{}. {}", i + 1, data_source))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Collects the name and network of the templates declared in the manifest,
+/// `None` if there is no manifest to validate the created data sources against.
+/// When a network is selected, the templates are moved to it like the data sources.
+pub(crate) fn manifest_templates(
+    manifest: &Path,
+    network: Option<&str>,
+) -> Option<Vec<(String, String)>> {
+    let templates = match manifest.to_str() {
+        Some(path) if manifest.is_file() => parser::collect_templates(path),
+        _ => return None,
+    };
+
+    Some(
+        templates
+            .into_iter()
+            .map(|(name, manifest_network)| {
+                (
+                    name,
+                    network.map_or(manifest_network, |network| network.to_owned()),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Collects the data sources declared in the manifest. When a network is selected, the data
+/// sources are moved to it and their addresses are taken from the networks file, which is
+/// in the `networks.json` format of graph-cli: `{network: {dataSourceName: {address}}}`.
+pub(crate) fn manifest_data_sources(
+    manifest: &Path,
+    networks: &Path,
+    network: Option<&str>,
+) -> Vec<ManifestDataSource> {
+    let mut data_sources = match manifest.to_str() {
+        Some(path) if manifest.is_file() => parser::collect_data_sources(path),
+        _ => return vec![],
+    };
+
+    if let Some(network) = network {
+        let addresses = match network_addresses(networks, network) {
+            Ok(addresses) => addresses,
+            Err(err) => {
+                logging::warning!(
+                    "Couldn't read the `{}` addresses from `{}`, the manifest addresses are used: {:#}",
+                    network,
+                    networks.display(),
+                    err
+                );
+                HashMap::new()
+            }
+        };
+
+        for data_source in data_sources.iter_mut() {
+            data_source.network = network.to_owned();
+            if let Some(address) = addresses.get(&data_source.name) {
+                data_source.address = Some(address.clone());
+            }
+        }
+    }
+
+    data_sources
+}
+
+/// Reads the addresses of the data sources on the network from the networks file.
+fn network_addresses(
+    networks: &Path,
+    network: &str,
+) -> Result<HashMap<String, String>, anyhow::Error> {
+    let content = std::fs::read_to_string(networks)?;
+    let json: serde_json::Value = serde_json::from_str(&content)?;
+    let data_sources = json
+        .get(network)
+        .and_then(|data_sources| data_sources.as_object())
+        .ok_or_else(|| anyhow!("network `{}` is not configured", network))?;
+
+    Ok(data_sources
+        .iter()
+        .filter_map(|(name, config)| {
+            config
+                .get("address")
+                .and_then(|address| address.as_str())
+                .map(|address| (name.clone(), address.to_owned()))
+        })
+        .collect())
+}
+
+/// Finds the data source whose handlers the test file tests: the one with the mapping file,
+/// or the name, of the test file (`gravity.test.ts` tests `src/gravity.ts`), then the one named
+/// after the folder of the test file, and last the only data source of the manifest.
+/// If none of several data sources matches, a warning is logged and `None` is returned.
+pub(crate) fn data_source_for_test<'a>(
+    data_sources: &'a [ManifestDataSource],
+    test_file: &Path,
+) -> Option<&'a ManifestDataSource> {
+    let stem = |path: &Path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .map(|name| name.to_lowercase())
+    };
+    let test_name = stem(test_file);
+    let folder = test_file
+        .parent()
+        .and_then(|folder| folder.file_name())
+        .and_then(|folder| folder.to_str())
+        .map(|folder| folder.to_lowercase());
+
+    data_sources
+        .iter()
+        .find(|data_source| {
+            test_name.is_some()
+                && (stem(Path::new(&data_source.mapping_file)) == test_name
+                    || Some(data_source.name.to_lowercase()) == test_name)
+        })
+        .or_else(|| {
+            data_sources.iter().find(|data_source| {
+                folder.is_some() && Some(data_source.name.to_lowercase()) == folder
+            })
+        })
+        .or_else(|| match data_sources {
+            [] => None,
+            [data_source] => Some(data_source),
+            _ => {
+                let names: Vec<&str> = data_sources
+                    .iter()
+                    .map(|data_source| data_source.name.as_str())
+                    .collect();
+                logging::warning!(
+                    "None of the data sources in the manifest ({}) is tested by `{}`, name the test file after the mapping file or the data source, or mock the `dataSource` values with `dataSourceMock.setReturnValues`",
+                    names.join(", "),
+                    test_file.display()
+                );
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod data_sources_tests {
+    use serial_test::serial;
+
+    use super::*;
+    use crate::logging::{accum, flush, LOGS};
+
+    fn data_source(name: &str, mapping_file: &str) -> ManifestDataSource {
+        ManifestDataSource {
+            name: name.to_owned(),
+            network: "mainnet".to_owned(),
+            address: None,
+            mapping_file: mapping_file.to_owned(),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn data_source_for_test_matches_mapping_file_name_folder_then_only_data_source() {
+        let data_sources = vec![
+            data_source("Gravity", "./src/gravity.ts"),
+            data_source("Factory", "./src/pool-factory.ts"),
+        ];
+        let tested = |test_file: &str| {
+            data_source_for_test(&data_sources, Path::new(test_file))
+                .map(|data_source| data_source.name.as_str())
+        };
+
+        assert_eq!(tested("tests/gravity.test.ts"), Some("Gravity"));
+        assert_eq!(tested("tests/pool-factory.test.ts"), Some("Factory"));
+        assert_eq!(tested("tests/factory.test.ts"), Some("Factory"));
+        assert_eq!(tested("tests/gravity/events.test.ts"), Some("Gravity"));
+        assert_eq!(
+            data_source_for_test(&data_sources[..1], Path::new("tests/other.test.ts"))
+                .map(|data_source| data_source.name.as_str()),
+            Some("Gravity")
+        );
+
+        accum();
+        assert_eq!(tested("tests/other.test.ts"), None);
+        unsafe {
+            let logs = LOGS.join("\n");
+            assert!(logs.contains(
+                "None of the data sources in the manifest (Gravity, Factory) is tested by `tests/other.test.ts`"
+            ));
+            flush();
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use graph::data::graphql::ext::DirectiveFinder;
 use graph_graphql::graphql_parser::schema;
 
-use crate::context::{MatchstickInstanceContext, SCHEMA};
+use crate::context::{get_entity_definition, MatchstickInstanceContext, SCHEMA};
 use crate::logging;
 
 pub(crate) fn derive_schema<C: graph::blockchain::Blockchain>(
@@ -10,11 +10,10 @@ pub(crate) fn derive_schema<C: graph::blockchain::Blockchain>(
     SCHEMA.definitions.iter().for_each(|def| {
         if let schema::Definition::TypeDefinition(schema::TypeDefinition::Object(o)) = def {
             let entity_type = &o.name;
-            let derived_fields = o.fields.iter().filter(|&f| {
-                matches!(f.field_type, schema::Type::NonNullType(..)) && f.is_derived()
-            });
+            let derived_fields = o.fields.iter().filter(|&f| f.is_derived());
             for f in derived_fields {
-                // field type is received as: '[ExampleClass!]!' and needs to be reduced to a class string
+                // field type is received as: '[ExampleClass!]!', '[ExampleClass]' or 'ExampleClass'
+                // and needs to be reduced to a class string
                 let clean_field_type = f.field_type.to_string().replace(['!', '[', ']'], "");
                 let mut directive = f.find_directive("derivedFrom").unwrap().clone();
 
@@ -53,3 +52,21 @@ pub(crate) fn derive_schema<C: graph::blockchain::Blockchain>(
         }
     });
 }
+
+/// Checks whether the derived field holds a single entity (one-to-one relation),
+/// e.g. `profile: Profile @derivedFrom(field: "owner")`, instead of a list of entities.
+/// Fields missing from the schema are treated as lists.
+pub(crate) fn is_single_derived_field(entity_type: &str, field_name: &str) -> bool {
+    get_entity_definition(entity_type)
+        .and_then(|o| o.fields.iter().find(|f| f.name == field_name))
+        .map(|f| !is_list_type(&f.field_type))
+        .unwrap_or(false)
+}
+
+pub(crate) fn is_list_type(field_type: &schema::Type<'static, String>) -> bool {
+    match field_type {
+        schema::Type::ListType(_) => true,
+        schema::Type::NonNullType(inner) => is_list_type(inner),
+        schema::Type::NamedType(_) => false,
+    }
+}
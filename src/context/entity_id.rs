@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use graph::data::store::{scalar::Bytes, Value};
+use graph::prelude::BigInt;
+
+use crate::context::get_entity_definition;
+
+/// The type of the `id` field of an entity as declared in the schema.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IdType {
+    String,
+    Bytes,
+    Int8,
+}
+
+impl IdType {
+    /// Looks up the type of the `id` field of `entity_type` in the schema.
+    /// Falls back to `ID` for entities which are not declared in the schema.
+    pub(crate) fn of(entity_type: &str) -> IdType {
+        let id_type = get_entity_definition(entity_type)
+            .and_then(|o| o.fields.iter().find(|f| f.name == "id"))
+            .map(|f| f.field_type.to_string().replace('!', ""));
+
+        match id_type.as_deref() {
+            Some("Bytes") => IdType::Bytes,
+            Some("Int8") => IdType::Int8,
+            _ => IdType::String,
+        }
+    }
+
+    /// Parses an id passed as a string from AssemblyScript into a value of this type.
+    /// `Bytes` ids are expected as hex strings (case-insensitive, with or without `0x`),
+    /// `Int8` ids as decimal strings. The graph-node version matchstick is built on
+    /// does not have an `Int8` value yet, so those ids are represented as `BigInt`.
+    pub(crate) fn parse(&self, id: &str) -> Result<Value, anyhow::Error> {
+        match self {
+            IdType::String => Ok(Value::String(id.to_owned())),
+            IdType::Bytes => Bytes::from_str(&id.to_ascii_lowercase())
+                .map(Value::Bytes)
+                .map_err(|err| anyhow!("`{}` is not a valid hex string: {}", id, err)),
+            IdType::Int8 => id
+                .parse::<i64>()
+                .map(|id| Value::BigInt(BigInt::from(id)))
+                .map_err(|err| anyhow!("`{}` is not a valid Int8: {}", id, err)),
+        }
+    }
+
+    /// Checks whether the value stored in the `id` field is of this type.
+    pub(crate) fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (IdType::String, Value::String(_)) | (IdType::Bytes, Value::Bytes(_)) => true,
+            (IdType::Int8, Value::Int(_)) => true,
+            (IdType::Int8, Value::BigInt(id)) => id.to_string().parse::<i64>().is_ok(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for IdType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdType::String => write!(f, "ID"),
+            IdType::Bytes => write!(f, "Bytes"),
+            IdType::Int8 => write!(f, "Int8"),
+        }
+    }
+}
+
+/// Returns the key under which an entity with the passed typed id is kept in the store.
+/// `Bytes` ids are kept under their lowercase `0x` prefixed hex string.
+pub(crate) fn id_key(id: &Value) -> Option<String> {
+    match id {
+        Value::String(id) => Some(id.clone()),
+        Value::Bytes(id) => Some(id.to_string()),
+        Value::Int(id) => Some(id.to_string()),
+        Value::BigInt(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Converts an id passed from AssemblyScript to the key of the entity in the store.
+/// Ids that can't be parsed according to the schema are used as they are,
+/// which means they won't match any stored entity.
+pub(crate) fn store_key(entity_type: &str, id: &str) -> String {
+    IdType::of(entity_type)
+        .parse(id)
+        .ok()
+        .and_then(|id| id_key(&id))
+        .unwrap_or_else(|| id.to_owned())
+}
+
+#[cfg(test)]
+mod entity_id_tests {
+    use super::*;
+
+    #[test]
+    fn parse_bytes_id_is_case_insensitive() {
+        let upper = IdType::Bytes.parse("0xABCD").unwrap();
+        let lower = IdType::Bytes.parse("abcd").unwrap();
+
+        assert_eq!(upper, lower);
+        assert_eq!(id_key(&upper), Some("0xabcd".to_owned()));
+    }
+
+    #[test]
+    fn parse_returns_error_for_invalid_ids() {
+        assert!(IdType::Bytes.parse("0xnothex").is_err());
+        assert!(IdType::Int8.parse("12a").is_err());
+        assert!(IdType::String.parse("anything").is_ok());
+    }
+
+    #[test]
+    fn matches_checks_the_value_type() {
+        assert!(IdType::Int8.matches(&Value::Int(1)));
+        assert!(IdType::Int8.matches(&IdType::Int8.parse("-9000000000").unwrap()));
+        assert!(!IdType::Bytes.matches(&Value::String("0xabcd".to_owned())));
+        assert!(!IdType::String.matches(&Value::Int(1)));
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use graph::data::graphql::ext::DirectiveFinder;
+
+use crate::context::conversion::get_fixture_value;
+use crate::context::{get_entity_definition, MatchstickInstanceContext};
+
+/// Loads the entities from a JSON or YAML fixture file into the store.
+/// The file is expected to be in the format `{EntityType: {id: {field: value}}}`.
+/// Field values are converted according to their schema type and every entity is
+/// validated and linked to its derived relations the same way `store.set` does it.
+pub(crate) fn load_fixture<C: graph::blockchain::Blockchain>(
+    context: &mut MatchstickInstanceContext<C>,
+    path: &str,
+) -> Result<(), anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read fixture file `{}`", path))?;
+    let fixture: serde_json::Value = if path.ends_with(".json") {
+        serde_json::from_str(&content)?
+    } else {
+        serde_yaml::from_str(&content)?
+    };
+
+    let entity_types = fixture
+        .as_object()
+        .ok_or_else(|| anyhow!("Expected a map of entity types in fixture `{}`", path))?;
+
+    for (entity_type, entities) in entity_types {
+        let definition = get_entity_definition(entity_type).ok_or_else(|| {
+            anyhow!(
+                "Entity type `{}` from fixture `{}` is not defined in the schema",
+                entity_type,
+                path
+            )
+        })?;
+        let entities = entities
+            .as_object()
+            .ok_or_else(|| anyhow!("Expected a map of ids for entity type `{}`", entity_type))?;
+
+        for (id, fields) in entities {
+            let fields = fields
+                .as_object()
+                .ok_or_else(|| anyhow!("Expected a map of fields for {} `{}`", entity_type, id))?;
+            let mut data = HashMap::new();
+
+            for (field_name, value) in fields {
+                let field = definition
+                    .fields
+                    .iter()
+                    .find(|f| &f.name == field_name)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Field `{}` is not defined on entity type `{}`",
+                            field_name,
+                            entity_type
+                        )
+                    })?;
+
+                if field.is_derived() {
+                    return Err(anyhow!(
+                        "Field `{}` of entity type `{}` is derived and can't be set from a fixture",
+                        field_name,
+                        entity_type
+                    ));
+                }
+
+                let value = get_fixture_value(value, &field.field_type).with_context(|| {
+                    format!(
+                        "Invalid value for field `{}` of {} `{}`",
+                        field_name, entity_type, id
+                    )
+                })?;
+                data.insert(field_name.clone(), value);
+            }
+
+            context
+                .store_set(entity_type.clone(), id.clone(), data)
+                .with_context(|| {
+                    format!(
+                        "Could not load {} `{}` from fixture `{}`",
+                        entity_type, id, path
+                    )
+                })?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1056 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use graph::data::store::Value;
+use graph::prelude::BigInt;
+use graph_graphql::graphql_parser::{query, schema};
+use graph_graphql::prelude::api_schema;
+use lazy_static::lazy_static;
+
+use crate::context::assertions::values_equal;
+use crate::context::conversion::get_fixture_value;
+use crate::context::derived_fields::{child_id_value, linked_ids};
+use crate::context::derived_schema::is_list_type;
+use crate::context::entity_id::store_key;
+use crate::context::{get_entity_definition, SCHEMA};
+
+type Store = HashMap<String, HashMap<String, HashMap<String, Value>>>;
+type Variables = serde_json::Map<String, serde_json::Value>;
+type Fragments<'a> = HashMap<&'a str, &'a query::FragmentDefinition<'static, String>>;
+type SelectionSet = query::SelectionSet<'static, String>;
+type Field = query::Field<'static, String>;
+/// An entity of the store with its type and store key.
+type Entity<'a> = (&'static str, &'a String, &'a HashMap<String, Value>);
+
+/// The operators graph-node generates filters for, as suffixes of the field names in the
+/// `<Type>_filter` input types of the API schema. `_` filters on the entities a field links to.
+const FILTER_OPERATORS: [&str; 20] = [
+    "_not",
+    "_gt",
+    "_lt",
+    "_gte",
+    "_lte",
+    "_in",
+    "_not_in",
+    "_contains",
+    "_contains_nocase",
+    "_not_contains",
+    "_not_contains_nocase",
+    "_starts_with",
+    "_starts_with_nocase",
+    "_not_starts_with",
+    "_not_starts_with_nocase",
+    "_ends_with",
+    "_ends_with_nocase",
+    "_not_ends_with",
+    "_not_ends_with_nocase",
+    "_",
+];
+
+/// The number of entities a collection field returns without a `first` argument.
+const DEFAULT_FIRST: u64 = 100;
+/// The largest `first` graph-node accepts by default (`GRAPH_GRAPHQL_MAX_FIRST`).
+const MAX_FIRST: u64 = 1000;
+/// The largest `skip` The Graph's hosted nodes accept (`GRAPH_GRAPHQL_MAX_SKIP`).
+const MAX_SKIP: u64 = 5000;
+
+lazy_static! {
+    /// The API schema graph-node generates from `schema.graphql`, with the query fields
+    /// of the entities, the arguments of the collection fields and the filter types.
+    static ref API_SCHEMA: Result<schema::Document<'static, String>, String> = {
+        let mut document = SCHEMA.clone();
+        // The scalars graph-node provides are not declared in `schema.graphql`
+        for scalar in ["BigDecimal", "BigInt", "Bytes", "Int8"] {
+            if type_definition(&document, scalar).is_none() {
+                document.definitions.push(schema::Definition::TypeDefinition(
+                    schema::TypeDefinition::Scalar(schema::ScalarType::new(scalar.to_owned())),
+                ));
+            }
+        }
+
+        api_schema(&document).map_err(|err| err.to_string())
+    };
+}
+
+/// The store as it was at the end of a committed block, for queries with a `block` argument.
+pub(crate) struct BlockStore<'a> {
+    pub(crate) number: u32,
+    pub(crate) hash: &'a str,
+    pub(crate) store: &'a Store,
+}
+
+/// Executes a GraphQL query against the store the way the subgraph's API would.
+/// The query is resolved with the query fields, argument defaults and filter types of the
+/// API schema graph-node generates from `schema.graphql`. Fields, arguments, filters and
+/// variables which the API schema doesn't declare fail the query, as they would on a node.
+/// Queries with a `block` argument are resolved against the store of a committed block.
+/// Returns the `data` of the response.
+pub(crate) fn execute_query<'a>(
+    store: &'a Store,
+    blocks: &[BlockStore<'a>],
+    query: &str,
+    variables: &Variables,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let api_schema = api_schema_document()?;
+    let document = query::parse_query::<String>(query)
+        .map_err(|err| anyhow!("Invalid query: {}", err))?
+        .into_static();
+
+    let mut operations = document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            query::Definition::Operation(operation) => Some(operation),
+            _ => None,
+        });
+    let (selection_set, variable_definitions) = match (operations.next(), operations.next()) {
+        (Some(query::OperationDefinition::SelectionSet(set)), None) => (set, &[][..]),
+        (Some(query::OperationDefinition::Query(q)), None) => {
+            (&q.selection_set, q.variable_definitions.as_slice())
+        }
+        (Some(query::OperationDefinition::Mutation(_)), None) => {
+            return Err(anyhow!(
+                "Mutations are not supported, subgraphs only serve queries"
+            ))
+        }
+        (Some(query::OperationDefinition::Subscription(_)), None) => {
+            return Err(anyhow!("Subscriptions are not supported by the mock store"))
+        }
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "The document contains several operations, only one can be run at a time"
+            ))
+        }
+        (None, _) => return Err(anyhow!("The document does not contain an operation")),
+    };
+
+    let fragments: Fragments = document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            query::Definition::Fragment(fragment) => Some((fragment.name.as_str(), fragment)),
+            _ => None,
+        })
+        .collect();
+    validate_variables(&document, variable_definitions, variables)?;
+    let variables = with_default_values(variables, variable_definitions)?;
+    let query_fields = type_fields(api_schema, "Query")
+        .ok_or_else(|| anyhow!("The API schema has no `Query` type"))?;
+
+    let executor = Executor {
+        store,
+        variables: &variables,
+        fragments: &fragments,
+    };
+    let mut data = serde_json::Map::new();
+
+    for (key, fields) in executor.collect_fields(&[selection_set], "Query")? {
+        let field = fields[0];
+        if field.name == "__typename" {
+            data.insert(key, "Query".into());
+            continue;
+        }
+        if field.name.starts_with("__") {
+            return Err(anyhow!(
+                "Introspection queries are not supported by the mock store"
+            ));
+        }
+
+        let definition = query_fields
+            .iter()
+            .find(|f| f.name == field.name)
+            .ok_or_else(|| anyhow!("Query has no field `{}`", field.name))?;
+        let entity_type = base_type_name(&definition.field_type);
+        let entity_types = object_types(entity_type);
+        if entity_types.is_empty() {
+            return Err(anyhow!(
+                "Field `{}` is not supported by the mock store",
+                field.name
+            ));
+        }
+
+        let arguments = executor.arguments(field, definition)?;
+        let executor = Executor {
+            store: block_store(store, blocks, arguments.get("block"))?,
+            ..executor
+        };
+        let selection_sets: Vec<&SelectionSet> = fields.iter().map(|f| &f.selection_set).collect();
+
+        let value = if is_list_type(&definition.field_type) {
+            let entities = entity_types
+                .iter()
+                .flat_map(|entity_type| {
+                    executor
+                        .store
+                        .get(*entity_type)
+                        .into_iter()
+                        .flat_map(move |entities| {
+                            entities.iter().map(move |(id, e)| (*entity_type, id, e))
+                        })
+                })
+                .collect();
+            executor.resolve_collection(entity_type, entities, &arguments, &selection_sets)?
+        } else {
+            let id = arguments
+                .get("id")
+                .map(|id| {
+                    id.as_str()
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| id.to_string())
+                })
+                .ok_or_else(|| anyhow!("Field `{}` requires an `id` argument", field.name))?;
+            let entity = entity_types.iter().find_map(|entity_type| {
+                executor.find_entity(&[*entity_type], &store_key(entity_type, &id))
+            });
+
+            match entity {
+                Some(entity) => executor.resolve_entity(entity, &selection_sets)?,
+                None => serde_json::Value::Null,
+            }
+        };
+
+        data.insert(key, value);
+    }
+
+    Ok(serde_json::Value::Object(data))
+}
+
+#[derive(Clone, Copy)]
+struct Executor<'a> {
+    store: &'a Store,
+    variables: &'a Variables,
+    fragments: &'a Fragments<'a>,
+}
+
+impl<'a> Executor<'a> {
+    /// Resolves the selected fields of a single entity.
+    fn resolve_entity(
+        &self,
+        (entity_type, id, entity): Entity<'a>,
+        selection_sets: &[&'a SelectionSet],
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        let definition = get_entity_definition(entity_type)
+            .ok_or_else(|| anyhow!("Type `{}` is not defined in the schema", entity_type))?;
+        let api_fields = type_fields(api_schema_document()?, entity_type)
+            .ok_or_else(|| anyhow!("Type `{}` is not defined in the API schema", entity_type))?;
+        let mut object = serde_json::Map::new();
+
+        for (key, fields) in self.collect_fields(selection_sets, entity_type)? {
+            let field = fields[0];
+            if field.name == "__typename" {
+                object.insert(key, entity_type.into());
+                continue;
+            }
+
+            let field_definition = definition
+                .fields
+                .iter()
+                .find(|f| f.name == field.name)
+                .ok_or_else(|| anyhow!("Type `{}` has no field `{}`", entity_type, field.name))?;
+            let api_definition = api_fields
+                .iter()
+                .find(|f| f.name == field.name)
+                .ok_or_else(|| anyhow!("Type `{}` has no field `{}`", entity_type, field.name))?;
+            let arguments = self.arguments(field, api_definition)?;
+            let value = field_value(id, entity, &field.name);
+
+            let linked_type = base_type_name(&field_definition.field_type);
+            let linked_types = object_types(linked_type);
+            // Scalars can't have subfields selected and linked entities must have some
+            let has_selection = fields.iter().any(|f| !f.selection_set.items.is_empty());
+            if linked_types.is_empty() == has_selection {
+                return Err(anyhow!(
+                    "Field `{}` of type `{}` {}",
+                    field.name,
+                    linked_type,
+                    if has_selection {
+                        "can't have a selection of subfields"
+                    } else {
+                        "must have a selection of subfields"
+                    }
+                ));
+            }
+
+            let resolved = if linked_types.is_empty() {
+                graphql_value(&value)
+            } else {
+                let selection_sets: Vec<&SelectionSet> =
+                    fields.iter().map(|f| &f.selection_set).collect();
+                let linked: Vec<Entity> = linked_ids(&value)
+                    .iter()
+                    .filter_map(|key| self.find_entity(&linked_types, key))
+                    .collect();
+
+                if is_list_type(&field_definition.field_type) {
+                    self.resolve_collection(linked_type, linked, &arguments, &selection_sets)?
+                } else {
+                    match linked.into_iter().next() {
+                        Some(linked) => self.resolve_entity(linked, &selection_sets)?,
+                        None => serde_json::Value::Null,
+                    }
+                }
+            };
+
+            object.insert(key, resolved);
+        }
+
+        Ok(serde_json::Value::Object(object))
+    }
+
+    /// Filters, sorts and paginates a list of entities and resolves the selected fields of each.
+    fn resolve_collection(
+        &self,
+        entity_type: &str,
+        mut entities: Vec<Entity<'a>>,
+        arguments: &Variables,
+        selection_sets: &[&'a SelectionSet],
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        if let Some(filter) = arguments.get("where").filter(|filter| !filter.is_null()) {
+            let filter = Filter::parse(entity_type, filter)?;
+            entities.retain(|entity| filter.matches(self, *entity));
+        }
+
+        let order_by = arguments.get("orderBy").and_then(|o| o.as_str());
+        let descending = arguments.get("orderDirection").and_then(|o| o.as_str()) == Some("desc");
+        entities.sort_by(|a, b| {
+            let ordering = match order_by {
+                Some(order_by) => compare_nullable(
+                    &self.order_value(*a, order_by),
+                    &self.order_value(*b, order_by),
+                ),
+                None => Ordering::Equal,
+            };
+            let ordering = ordering.then_with(|| {
+                compare_values(&child_id_value(a.1, a.2), &child_id_value(b.1, b.2))
+                    .unwrap_or_else(|| a.1.cmp(b.1))
+            });
+
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        // Paginated like graph-node, which rejects `first` and `skip` values above its limits
+        let skip = bounded_argument(arguments, "skip", 0, MAX_SKIP)?;
+        let first = bounded_argument(arguments, "first", DEFAULT_FIRST, MAX_FIRST)?;
+
+        entities
+            .into_iter()
+            .skip(skip as usize)
+            .take(first as usize)
+            .map(|entity| self.resolve_entity(entity, selection_sets))
+            .collect::<Result<Vec<serde_json::Value>, _>>()
+            .map(serde_json::Value::Array)
+    }
+
+    /// The value an entity is sorted by, `orderBy: owner__name` sorts by a field of a linked entity.
+    fn order_value(&self, (entity_type, id, entity): Entity<'a>, order_by: &str) -> Value {
+        match order_by.split_once("__") {
+            Some((field, child_field)) => {
+                let linked_types = get_entity_definition(entity_type)
+                    .and_then(|definition| definition.fields.iter().find(|f| f.name == field))
+                    .map(|field| object_types(base_type_name(&field.field_type)))
+                    .unwrap_or_default();
+
+                linked_ids(&field_value(id, entity, field))
+                    .iter()
+                    .find_map(|key| self.find_entity(&linked_types, key))
+                    .map(|(_, id, entity)| field_value(id, entity, child_field))
+                    .unwrap_or(Value::Null)
+            }
+            None => field_value(id, entity, order_by),
+        }
+    }
+
+    /// Finds the entity with the store key among the entities of the types.
+    fn find_entity(&self, entity_types: &[&'static str], key: &str) -> Option<Entity<'a>> {
+        entity_types.iter().find_map(|entity_type| {
+            self.store
+                .get(*entity_type)
+                .and_then(|entities| entities.get_key_value(key))
+                .map(|(id, entity)| (*entity_type, id, entity))
+        })
+    }
+
+    /// Collects the fields selected on an object of the type by response key, expanding fragments
+    /// and skipping the fields excluded by `@skip` and `@include`.
+    /// Fields selected several times under the same key have their selections merged.
+    fn collect_fields(
+        &self,
+        selection_sets: &[&'a SelectionSet],
+        type_name: &str,
+    ) -> Result<Vec<(String, Vec<&'a Field>)>, anyhow::Error> {
+        let mut fields = vec![];
+        for selection_set in selection_sets {
+            self.collect_fields_into(selection_set, type_name, &mut fields)?;
+        }
+        Ok(fields)
+    }
+
+    fn collect_fields_into(
+        &self,
+        selection_set: &'a SelectionSet,
+        type_name: &str,
+        fields: &mut Vec<(String, Vec<&'a Field>)>,
+    ) -> Result<(), anyhow::Error> {
+        for selection in selection_set.items.iter() {
+            match selection {
+                query::Selection::Field(field) => {
+                    if !self.is_included(&field.directives)? {
+                        continue;
+                    }
+                    let key = field.alias.clone().unwrap_or_else(|| field.name.clone());
+                    match fields.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, same_key)) => same_key.push(field),
+                        None => fields.push((key, vec![field])),
+                    }
+                }
+                query::Selection::FragmentSpread(spread) => {
+                    if !self.is_included(&spread.directives)? {
+                        continue;
+                    }
+                    let fragment = self
+                        .fragments
+                        .get(spread.fragment_name.as_str())
+                        .ok_or_else(|| anyhow!("Unknown fragment `{}`", spread.fragment_name))?;
+                    let query::TypeCondition::On(condition) = &fragment.type_condition;
+                    if type_condition_applies(condition, type_name) {
+                        self.collect_fields_into(&fragment.selection_set, type_name, fields)?;
+                    }
+                }
+                query::Selection::InlineFragment(fragment) => {
+                    if !self.is_included(&fragment.directives)? {
+                        continue;
+                    }
+                    let applies = match &fragment.type_condition {
+                        Some(query::TypeCondition::On(condition)) => {
+                            type_condition_applies(condition, type_name)
+                        }
+                        None => true,
+                    };
+                    if applies {
+                        self.collect_fields_into(&fragment.selection_set, type_name, fields)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the `@skip` and `@include` directives of a selection.
+    fn is_included(
+        &self,
+        directives: &[query::Directive<'static, String>],
+    ) -> Result<bool, anyhow::Error> {
+        for directive in directives {
+            let condition = directive
+                .arguments
+                .iter()
+                .find(|(name, _)| name == "if")
+                .map(|(_, value)| input_value(value, self.variables))
+                .transpose()?;
+
+            match (directive.name.as_str(), condition) {
+                ("skip", Some(serde_json::Value::Bool(true)))
+                | ("include", Some(serde_json::Value::Bool(false))) => return Ok(false),
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
+    /// Converts the arguments of a field to JSON, replacing the variables with their values
+    /// and filling in the defaults the API schema declares for the arguments which aren't passed.
+    fn arguments(
+        &self,
+        field: &Field,
+        definition: &schema::Field<'static, String>,
+    ) -> Result<Variables, anyhow::Error> {
+        if let Some((name, _)) = field
+            .arguments
+            .iter()
+            .find(|(name, _)| !definition.arguments.iter().any(|a| a.name == *name))
+        {
+            return Err(anyhow!(
+                "Unknown argument `{}` on field `{}`",
+                name,
+                field.name
+            ));
+        }
+
+        let mut arguments = Variables::new();
+        for argument in definition.arguments.iter() {
+            let value = field
+                .arguments
+                .iter()
+                .find(|(name, _)| *name == argument.name)
+                .map(|(_, value)| value)
+                .or(argument.default_value.as_ref());
+
+            match value {
+                Some(value) => {
+                    arguments.insert(argument.name.clone(), input_value(value, self.variables)?);
+                }
+                None if matches!(argument.value_type, schema::Type::NonNullType(_)) => {
+                    return Err(anyhow!(
+                        "Field `{}` requires the argument `{}`",
+                        field.name,
+                        argument.name
+                    ));
+                }
+                None => {}
+            }
+        }
+        Ok(arguments)
+    }
+}
+
+/// A `where` argument, e.g. `{ name_starts_with: "a", owner_: { name: "b" } }`.
+enum Filter {
+    All(Vec<Filter>),
+    Any(Vec<Filter>),
+    /// A condition on a field of the entity
+    Field {
+        field: String,
+        operator: &'static str,
+        value: Value,
+    },
+    /// A filter on the entities a field links to, which matches if any of them matches
+    Linked {
+        field: String,
+        entity_types: Vec<&'static str>,
+        filter: Box<Filter>,
+    },
+}
+
+impl Filter {
+    fn parse(entity_type: &str, filter: &serde_json::Value) -> Result<Filter, anyhow::Error> {
+        filter
+            .as_object()
+            .ok_or_else(|| anyhow!("The filter of `{}` must be an object", entity_type))?
+            .iter()
+            .map(|(key, value)| Filter::parse_condition(entity_type, key, value))
+            .collect::<Result<Vec<Filter>, _>>()
+            .map(Filter::All)
+    }
+
+    fn parse_condition(
+        entity_type: &str,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<Filter, anyhow::Error> {
+        if key == "and" || key == "or" {
+            let filters = value
+                .as_array()
+                .ok_or_else(|| anyhow!("The `{}` filter must be a list", key))?
+                .iter()
+                .map(|filter| Filter::parse(entity_type, filter))
+                .collect::<Result<Vec<Filter>, _>>()?;
+            return Ok(if key == "and" {
+                Filter::All(filters)
+            } else {
+                Filter::Any(filters)
+            });
+        }
+
+        let fields = type_fields(&SCHEMA, entity_type)
+            .ok_or_else(|| anyhow!("Type `{}` is not defined in the schema", entity_type))?;
+        // The key is a field name followed by an operator, fields whose names are prefixes
+        // of other field names (e.g. `name` and `name_not`) are told apart by the longest match
+        let (field, operator) = fields
+            .iter()
+            .filter_map(|field| {
+                let suffix = key.strip_prefix(field.name.as_str())?;
+                if suffix.is_empty() {
+                    return Some((field, ""));
+                }
+                FILTER_OPERATORS
+                    .iter()
+                    .find(|operator| **operator == suffix)
+                    .map(|operator| (field, *operator))
+            })
+            .max_by_key(|(field, _)| field.name.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "The filter `{}` of `{}` is not supported by the mock store",
+                    key,
+                    entity_type
+                )
+            })?;
+
+        if operator == "_" {
+            let linked_type = base_type_name(&field.field_type);
+            return Ok(Filter::Linked {
+                field: field.name.clone(),
+                entity_types: object_types(linked_type),
+                filter: Box::new(Filter::parse(linked_type, value)?),
+            });
+        }
+
+        // `_in` and `_not_in` take a list of values of the field's type
+        let value_type = match operator {
+            "_in" | "_not_in" => schema::Type::ListType(Box::new(field.field_type.clone())),
+            _ => field.field_type.clone(),
+        };
+        let value = get_fixture_value(value, &value_type)
+            .with_context(|| format!("Invalid value for filter `{}`", key))?;
+
+        Ok(Filter::Field {
+            field: field.name.clone(),
+            operator,
+            value,
+        })
+    }
+
+    fn matches(&self, executor: &Executor, entity: Entity) -> bool {
+        let (_, id, fields) = entity;
+        match self {
+            // The nested filters apply to the same entity, with its type
+            Filter::All(filters) => filters
+                .iter()
+                .all(|filter| filter.matches(executor, entity)),
+            Filter::Any(filters) => filters
+                .iter()
+                .any(|filter| filter.matches(executor, entity)),
+            Filter::Field {
+                field,
+                operator,
+                value,
+            } => operator_matches(operator, &field_value(id, fields, field), value),
+            Filter::Linked {
+                field,
+                entity_types,
+                filter,
+            } => linked_ids(&field_value(id, fields, field))
+                .iter()
+                .filter_map(|key| executor.find_entity(entity_types, key))
+                .any(|linked| filter.matches(executor, linked)),
+        }
+    }
+}
+
+fn operator_matches(operator: &str, actual: &Value, expected: &Value) -> bool {
+    let starts_with = |a: &str, e: &str| a.starts_with(e);
+    let ends_with = |a: &str, e: &str| a.ends_with(e);
+
+    match operator {
+        "" => values_equal(expected, actual),
+        "_not" => !values_equal(expected, actual),
+        "_gt" => compare_values(actual, expected) == Some(Ordering::Greater),
+        "_gte" => matches!(
+            compare_values(actual, expected),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        "_lt" => compare_values(actual, expected) == Some(Ordering::Less),
+        "_lte" => matches!(
+            compare_values(actual, expected),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        "_in" => list_contains(expected, actual),
+        "_not_in" => !list_contains(expected, actual),
+        "_contains" => contains(actual, expected, false),
+        "_contains_nocase" => contains(actual, expected, true),
+        "_not_contains" => !contains(actual, expected, false),
+        "_not_contains_nocase" => !contains(actual, expected, true),
+        "_starts_with" => string_matches(actual, expected, false, starts_with),
+        "_starts_with_nocase" => string_matches(actual, expected, true, starts_with),
+        "_not_starts_with" => !string_matches(actual, expected, false, starts_with),
+        "_not_starts_with_nocase" => !string_matches(actual, expected, true, starts_with),
+        "_ends_with" => string_matches(actual, expected, false, ends_with),
+        "_ends_with_nocase" => string_matches(actual, expected, true, ends_with),
+        "_not_ends_with" => !string_matches(actual, expected, false, ends_with),
+        "_not_ends_with_nocase" => !string_matches(actual, expected, true, ends_with),
+        _ => false,
+    }
+}
+
+fn list_contains(list: &Value, value: &Value) -> bool {
+    match list {
+        Value::List(values) => values.iter().any(|v| values_equal(v, value)),
+        _ => false,
+    }
+}
+
+/// Substring match for strings, every expected element is present for lists.
+fn contains(actual: &Value, expected: &Value, nocase: bool) -> bool {
+    match (actual, expected) {
+        (Value::String(_), Value::String(_)) => {
+            string_matches(actual, expected, nocase, |a, e| a.contains(e))
+        }
+        (Value::List(_), Value::List(expected)) => {
+            expected.iter().all(|value| list_contains(actual, value))
+        }
+        _ => false,
+    }
+}
+
+fn string_matches(
+    actual: &Value,
+    expected: &Value,
+    nocase: bool,
+    matches: impl Fn(&str, &str) -> bool,
+) -> bool {
+    match (actual, expected) {
+        (Value::String(actual), Value::String(expected)) if nocase => {
+            matches(&actual.to_lowercase(), &expected.to_lowercase())
+        }
+        (Value::String(actual), Value::String(expected)) => matches(actual, expected),
+        _ => false,
+    }
+}
+
+/// Orders values of the same type, `Int` and `BigInt` values are compared as numbers.
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+        (Value::Int(a), Value::BigInt(b)) => BigInt::from(*a).partial_cmp(b),
+        (Value::BigInt(a), Value::Int(b)) => a.partial_cmp(&BigInt::from(*b)),
+        (Value::BigInt(a), Value::BigInt(b)) => a.partial_cmp(b),
+        (Value::BigDecimal(a), Value::BigDecimal(b)) => a.partial_cmp(b),
+        (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+        (Value::Bytes(a), Value::Bytes(b)) => a.as_slice().partial_cmp(b.as_slice()),
+        (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// Same as `compare_values`, but sorts null values after all other values.
+fn compare_nullable(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (a, b) => compare_values(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Converts a store value to the JSON the GraphQL API returns for it.
+/// BigInt, BigDecimal and Bytes values are returned as strings.
+fn graphql_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(string) => serde_json::Value::from(string.clone()),
+        Value::Int(int) => serde_json::Value::from(*int),
+        Value::BigDecimal(decimal) => serde_json::Value::from(decimal.to_string()),
+        Value::Bool(bool) => serde_json::Value::from(*bool),
+        Value::List(values) => serde_json::Value::Array(values.iter().map(graphql_value).collect()),
+        Value::Null => serde_json::Value::Null,
+        Value::Bytes(bytes) => serde_json::Value::from(bytes.to_string()),
+        Value::BigInt(int) => serde_json::Value::from(int.to_string()),
+    }
+}
+
+/// The value of a field of an entity, the id is taken with its type.
+fn field_value(id: &str, entity: &HashMap<String, Value>, field: &str) -> Value {
+    match field {
+        "id" => child_id_value(id, entity),
+        field => entity.get(field).cloned().unwrap_or(Value::Null),
+    }
+}
+
+/// Converts a query value to JSON, replacing the variables with their values.
+fn input_value(
+    value: &query::Value<'static, String>,
+    variables: &Variables,
+) -> Result<serde_json::Value, anyhow::Error> {
+    Ok(match value {
+        query::Value::Variable(name) => variables
+            .get(name)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null),
+        query::Value::Int(number) => number
+            .as_i64()
+            .map(serde_json::Value::from)
+            .ok_or_else(|| anyhow!("Int value {:?} is out of range", number))?,
+        query::Value::Float(float) => serde_json::Value::from(*float),
+        query::Value::String(string) => serde_json::Value::from(string.clone()),
+        query::Value::Boolean(bool) => serde_json::Value::from(*bool),
+        query::Value::Null => serde_json::Value::Null,
+        query::Value::Enum(value) => serde_json::Value::from(value.clone()),
+        query::Value::List(values) => serde_json::Value::Array(
+            values
+                .iter()
+                .map(|value| input_value(value, variables))
+                .collect::<Result<_, _>>()?,
+        ),
+        query::Value::Object(values) => serde_json::Value::Object(
+            values
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), input_value(value, variables)?)))
+                .collect::<Result<_, anyhow::Error>>()?,
+        ),
+    })
+}
+
+/// Checks that the variables used in the document are the ones the operation defines,
+/// and that the non-null variables without a default value are passed.
+fn validate_variables(
+    document: &query::Document<'static, String>,
+    definitions: &[query::VariableDefinition<'static, String>],
+    variables: &Variables,
+) -> Result<(), anyhow::Error> {
+    let mut used = vec![];
+    for definition in document.definitions.iter() {
+        match definition {
+            query::Definition::Operation(query::OperationDefinition::SelectionSet(set)) => {
+                used_variables(set, &mut used)
+            }
+            query::Definition::Operation(query::OperationDefinition::Query(q)) => {
+                used_variables(&q.selection_set, &mut used)
+            }
+            query::Definition::Fragment(fragment) => {
+                used_variables(&fragment.selection_set, &mut used)
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(name) = used
+        .iter()
+        .find(|name| !definitions.iter().any(|d| d.name == **name))
+    {
+        return Err(anyhow!("Variable `${}` is not defined", name));
+    }
+    for definition in definitions {
+        if !used.contains(&definition.name.as_str()) {
+            return Err(anyhow!("Variable `${}` is never used", definition.name));
+        }
+        let passed = variables
+            .get(&definition.name)
+            .map_or(false, |value| !value.is_null());
+        if !passed
+            && definition.default_value.is_none()
+            && matches!(definition.var_type, query::Type::NonNullType(_))
+        {
+            return Err(anyhow!(
+                "Variable `${}` is required but was not passed",
+                definition.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Collects the names of the variables used in the arguments and directives of a selection set.
+fn used_variables<'a>(selection_set: &'a SelectionSet, used: &mut Vec<&'a str>) {
+    fn value_variables<'a>(value: &'a query::Value<'static, String>, used: &mut Vec<&'a str>) {
+        match value {
+            query::Value::Variable(name) => used.push(name),
+            query::Value::List(values) => {
+                values.iter().for_each(|value| value_variables(value, used))
+            }
+            query::Value::Object(fields) => fields
+                .values()
+                .for_each(|value| value_variables(value, used)),
+            _ => {}
+        }
+    }
+    let directive_variables = |directives: &'a [query::Directive<'static, String>],
+                               used: &mut Vec<&'a str>| {
+        directives
+            .iter()
+            .flat_map(|directive| directive.arguments.iter())
+            .for_each(|(_, value)| value_variables(value, used))
+    };
+
+    for selection in selection_set.items.iter() {
+        match selection {
+            query::Selection::Field(field) => {
+                field
+                    .arguments
+                    .iter()
+                    .for_each(|(_, value)| value_variables(value, used));
+                directive_variables(&field.directives, used);
+                used_variables(&field.selection_set, used);
+            }
+            query::Selection::FragmentSpread(spread) => {
+                directive_variables(&spread.directives, used)
+            }
+            query::Selection::InlineFragment(fragment) => {
+                directive_variables(&fragment.directives, used);
+                used_variables(&fragment.selection_set, used);
+            }
+        }
+    }
+}
+
+/// Adds the default values of the variables of the operation which are not passed.
+fn with_default_values(
+    variables: &Variables,
+    definitions: &[query::VariableDefinition<'static, String>],
+) -> Result<Variables, anyhow::Error> {
+    let mut variables = variables.clone();
+    for definition in definitions {
+        if let (None, Some(default)) = (
+            variables.get(&definition.name),
+            definition.default_value.as_ref(),
+        ) {
+            let default = input_value(default, &Variables::new())?;
+            variables.insert(definition.name.clone(), default);
+        }
+    }
+    Ok(variables)
+}
+
+/// Picks the store a root field is resolved against: for a `block` argument the store as it was
+/// at the end of the committed block, otherwise the current store.
+fn block_store<'a>(
+    store: &'a Store,
+    blocks: &[BlockStore<'a>],
+    block: Option<&serde_json::Value>,
+) -> Result<&'a Store, anyhow::Error> {
+    let block = match block.and_then(|block| block.as_object()) {
+        Some(block) => block,
+        None => return Ok(store),
+    };
+    let last = blocks.last().ok_or_else(|| {
+        anyhow!("Queries with a `block` argument need blocks committed with `commitBlock`")
+    })?;
+
+    if let Some(hash) = block.get("hash").and_then(|hash| hash.as_str()) {
+        return blocks
+            .iter()
+            .find(|block| block.hash.eq_ignore_ascii_case(hash))
+            .map(|block| block.store)
+            .ok_or_else(|| anyhow!("Block with hash `{}` has not been committed", hash));
+    }
+    if let Some(number) = block.get("number").and_then(|number| number.as_u64()) {
+        if number > last.number as u64 {
+            return Err(anyhow!(
+                "Block {} has not been committed, the last committed block is {}",
+                number,
+                last.number
+            ));
+        }
+        // The store of a block is the store of the last committed block up to it
+        return blocks
+            .iter()
+            .rev()
+            .find(|block| block.number as u64 <= number)
+            .map(|block| block.store)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Block {} is before the first committed block {}",
+                    number,
+                    blocks[0].number
+                )
+            });
+    }
+    if let Some(number) = block.get("number_gte").and_then(|number| number.as_u64()) {
+        if number > last.number as u64 {
+            return Err(anyhow!(
+                "Block {} has not been committed, the last committed block is {}",
+                number,
+                last.number
+            ));
+        }
+    }
+
+    Ok(store)
+}
+
+fn api_schema_document() -> Result<&'static schema::Document<'static, String>, anyhow::Error> {
+    API_SCHEMA.as_ref().map_err(|err| {
+        anyhow!(
+            "Could not generate the API schema from `schema.graphql`: {}",
+            err
+        )
+    })
+}
+
+fn type_definition<'d>(
+    document: &'d schema::Document<'static, String>,
+    name: &str,
+) -> Option<&'d schema::TypeDefinition<'static, String>> {
+    document
+        .definitions
+        .iter()
+        .find_map(|definition| match definition {
+            schema::Definition::TypeDefinition(type_definition) => {
+                let type_name = match type_definition {
+                    schema::TypeDefinition::Scalar(t) => &t.name,
+                    schema::TypeDefinition::Object(t) => &t.name,
+                    schema::TypeDefinition::Interface(t) => &t.name,
+                    schema::TypeDefinition::Union(t) => &t.name,
+                    schema::TypeDefinition::Enum(t) => &t.name,
+                    schema::TypeDefinition::InputObject(t) => &t.name,
+                };
+                (type_name == name).then(|| type_definition)
+            }
+            _ => None,
+        })
+}
+
+/// The fields of an object or interface type.
+fn type_fields<'d>(
+    document: &'d schema::Document<'static, String>,
+    name: &str,
+) -> Option<&'d Vec<schema::Field<'static, String>>> {
+    match type_definition(document, name)? {
+        schema::TypeDefinition::Object(object) => Some(&object.fields),
+        schema::TypeDefinition::Interface(interface) => Some(&interface.fields),
+        _ => None,
+    }
+}
+
+/// The entity types the values of a type are stored as: the type itself for entity types,
+/// the types which implement it for interfaces, none for other types.
+fn object_types(type_name: &str) -> Vec<&'static str> {
+    SCHEMA
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            schema::Definition::TypeDefinition(schema::TypeDefinition::Object(o))
+                if o.name == type_name
+                    || o.implements_interfaces.iter().any(|i| i == type_name) =>
+            {
+                Some(o.name.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether a fragment on the type condition applies to an object of the type.
+fn type_condition_applies(condition: &str, type_name: &str) -> bool {
+    condition == type_name
+        || get_entity_definition(type_name).map_or(false, |definition| {
+            definition
+                .implements_interfaces
+                .iter()
+                .any(|interface| interface == condition)
+        })
+}
+
+/// Reads a pagination argument, which defaults to `default` and must be between 0 and `max`.
+fn bounded_argument(
+    arguments: &Variables,
+    name: &str,
+    default: u64,
+    max: u64,
+) -> Result<u64, anyhow::Error> {
+    let value = match arguments.get(name).filter(|value| !value.is_null()) {
+        Some(value) => value,
+        None => return Ok(default),
+    };
+
+    match value.as_u64() {
+        Some(number) if number <= max => Ok(number),
+        _ => Err(anyhow!(
+            "The `{}` argument must be between 0 and {}, but is {}",
+            name,
+            max,
+            value
+        )),
+    }
+}
+
+fn base_type_name(field_type: &schema::Type<'static, String>) -> &str {
+    match field_type {
+        schema::Type::NamedType(name) => name,
+        schema::Type::ListType(inner) | schema::Type::NonNullType(inner) => base_type_name(inner),
+    }
+}
@@ -0,0 +1,203 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use graph::prelude::ethabi::{Address, ParamType, Token};
+
+use crate::context::contract_calls::{ContractCall, ExactMock};
+use crate::context::conversion::{get_fixture_token, signature_types};
+use crate::context::mock_result::{MockResult, RevertReason};
+use crate::context::MatchstickInstanceContext;
+
+/// Loads the contract calls mocked in a JSON or YAML fixture file, the same way `mockFunction` does.
+/// The file is expected to be a list of contracts in the format:
+/// ```yaml
+/// - address: "0x2E645469f354BB4F5c8a05B3b30A929361cf77eC"
+///   functions:
+///     - function: getGravatar(address):(string,string)
+///       calls:
+///         - args: ["0x0000000000000000000000000000000000000001"]
+///           returns: ["Gravatar", "https://example.com/gravatar.png"]
+///         - args: ["0x0000000000000000000000000000000000000002"]
+///           revert: "Gravatar doesn't exist"
+/// ```
+/// `function` is either the function signature or only its name, then the signature is derived
+/// from the manifest ABIs. Values are typed by the signature, a call can revert instead of returning
+/// values with `revert: true`, or with the reason string under `revert`.
+pub(crate) fn load_mock_fixture<C: graph::blockchain::Blockchain>(
+    context: &mut MatchstickInstanceContext<C>,
+    path: &str,
+) -> Result<(), anyhow::Error> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read mock fixture file `{}`", path))?;
+    let fixture: serde_json::Value = if path.ends_with(".json") {
+        serde_json::from_str(&content)?
+    } else {
+        serde_yaml::from_str(&content)?
+    };
+
+    let contracts = fixture
+        .as_array()
+        .ok_or_else(|| anyhow!("Expected a list of contracts in mock fixture `{}`", path))?;
+
+    for contract in contracts {
+        let address = contract
+            .get("address")
+            .and_then(|address| address.as_str())
+            .ok_or_else(|| anyhow!("Expected an `address` for every contract in `{}`", path))?;
+        let contract_address = Address::from_str(address)
+            .map_err(|err| anyhow!("Invalid contract address `{}`: {}", address, err))?;
+        let functions = contract
+            .get("functions")
+            .and_then(|functions| functions.as_array())
+            .ok_or_else(|| anyhow!("Expected a list of `functions` for contract `{}`", address))?;
+
+        for function in functions {
+            let fn_name_or_signature = function
+                .get("function")
+                .and_then(|function| function.as_str())
+                .ok_or_else(|| {
+                    anyhow!("Expected a `function` for every function of `{}`", address)
+                })?;
+            let calls = function
+                .get("calls")
+                .and_then(|calls| calls.as_array())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Expected a list of `calls` for `{}` of `{}`",
+                        fn_name_or_signature,
+                        address
+                    )
+                })?;
+
+            for call in calls {
+                mock_call(context, contract_address, fn_name_or_signature, call).with_context(
+                    || {
+                        format!(
+                            "Invalid mock of `{}` on `{}` in mock fixture `{}`",
+                            fn_name_or_signature, address, path
+                        )
+                    },
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts the arguments and the result of a mocked call from the fixture and mocks the call.
+fn mock_call<C: graph::blockchain::Blockchain>(
+    context: &mut MatchstickInstanceContext<C>,
+    contract_address: Address,
+    fn_name_or_signature: &str,
+    call: &serde_json::Value,
+) -> Result<(), anyhow::Error> {
+    let empty = vec![];
+    let args = list(call, "args")?.unwrap_or(&empty);
+
+    let (fn_name, fn_signature) = match fn_name_or_signature.split_once('(') {
+        Some((fn_name, _)) => (fn_name.to_owned(), fn_name_or_signature.to_owned()),
+        None => (
+            fn_name_or_signature.to_owned(),
+            context
+                .manifest
+                .abis
+                .signature_by_arity(fn_name_or_signature, args.len())?,
+        ),
+    };
+    let (inputs, outputs) = signature_types(&fn_signature);
+
+    let args = tokens(args, &inputs, "arguments")?;
+    let fn_signature = context
+        .manifest
+        .abis
+        .resolve_signature(&fn_name, &fn_signature, &args)?;
+
+    let revert = match call.get("revert") {
+        None | Some(serde_json::Value::Bool(false)) => None,
+        Some(serde_json::Value::Bool(true)) => Some(RevertReason::Unknown),
+        Some(serde_json::Value::String(reason)) => Some(RevertReason::Message(reason.clone())),
+        Some(_) => {
+            return Err(anyhow!(
+                "Expected `revert` to be `true` or the revert reason string"
+            ))
+        }
+    };
+    if revert.is_some() && call.get("returns").is_some() {
+        return Err(anyhow!(
+            "A call can either `revert` or have `returns` values, not both"
+        ));
+    }
+
+    let result = match revert {
+        Some(reason) => MockResult::Revert(reason),
+        None => {
+            let returns = list(call, "returns")?.unwrap_or(&empty);
+            let outputs = match outputs {
+                Some(outputs) => outputs,
+                None if returns.is_empty() => vec![],
+                None => {
+                    return Err(anyhow!(
+                        "The signature `{}` should declare the types of the return values",
+                        fn_signature
+                    ))
+                }
+            };
+            MockResult::Return(tokens(returns, &outputs, "return values")?)
+        }
+    };
+
+    let call = ContractCall {
+        contract_address,
+        fn_name,
+        fn_signature,
+        args,
+    };
+    let fn_id = MatchstickInstanceContext::<C>::call_fn_id(&call);
+    context
+        .exact_mocks
+        .insert(fn_id.clone(), ExactMock { call, hits: 0 });
+    context.fn_ret_map.insert(fn_id, result);
+
+    Ok(())
+}
+
+/// Gets the list under the key, `None` if the key is missing.
+fn list<'a>(
+    value: &'a serde_json::Value,
+    key: &str,
+) -> Result<Option<&'a Vec<serde_json::Value>>, anyhow::Error> {
+    value
+        .get(key)
+        .map(|list| {
+            list.as_array()
+                .ok_or_else(|| anyhow!("Expected `{}` to be a list of values", key))
+        })
+        .transpose()
+}
+
+/// Converts the values into tokens of the types from the signature.
+fn tokens(
+    values: &[serde_json::Value],
+    param_types: &[ParamType],
+    kind: &str,
+) -> Result<Vec<Token>, anyhow::Error> {
+    if values.len() != param_types.len() {
+        return Err(anyhow!(
+            "Expected {} {}, but received {}",
+            param_types.len(),
+            kind,
+            values.len()
+        ));
+    }
+
+    values
+        .iter()
+        .zip(param_types.iter())
+        .enumerate()
+        .map(|(index, (value, param_type))| {
+            get_fixture_token(value, param_type)
+                .with_context(|| format!("Invalid value at position {} of the {}", index + 1, kind))
+        })
+        .collect()
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use graph::data::store::scalar::Bytes;
+use graph::prelude::ethabi::Token;
+
+/// The result of a mocked contract call.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum MockResult {
+    Return(Vec<Token>),
+    Revert(RevertReason),
+}
+
+/// Why a mocked contract call reverted.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum RevertReason {
+    /// Mocked with `reverts: true`, without a reason
+    Unknown,
+    /// `revert("reason")` or a failed `require(condition, "reason")`
+    Message(String),
+    /// A custom error, ABI-encoded with its 4 bytes selector
+    CustomError(Bytes),
+    OutOfGas,
+}
+
+impl RevertReason {
+    /// Parses the kind of a mocked revert and its data: `reason` with the reason string,
+    /// `customError` with the ABI-encoded error as a hex string, or `outOfGas` without data.
+    pub(crate) fn parse(kind: &str, data: &str) -> Result<RevertReason, anyhow::Error> {
+        match kind {
+            "" => Ok(RevertReason::Unknown),
+            "reason" => Ok(RevertReason::Message(data.to_owned())),
+            "customError" => {
+                let error = Bytes::from_str(&data.to_ascii_lowercase())
+                    .map_err(|err| anyhow!("Invalid custom error data `{}`: {}", data, err))?;
+                if error.as_slice().len() < 4 {
+                    return Err(anyhow!(
+                        "Custom error data `{}` should start with the 4 bytes error selector",
+                        data
+                    ));
+                }
+                Ok(RevertReason::CustomError(error))
+            }
+            "outOfGas" => Ok(RevertReason::OutOfGas),
+            _ => Err(anyhow!(
+                "Unknown revert kind `{}`, expected one of: reason, customError, outOfGas",
+                kind
+            )),
+        }
+    }
+
+    /// Checks whether the call reverted with the expected reason: the reason string,
+    /// the custom error data or only its selector as hex, or `outOfGas`.
+    pub(crate) fn matches(&self, expected: &str) -> bool {
+        match self {
+            RevertReason::Unknown => false,
+            RevertReason::Message(message) => message == expected,
+            RevertReason::CustomError(error) => {
+                let selector = Bytes::from(&error.as_slice()[..4]);
+                error.to_string().eq_ignore_ascii_case(expected)
+                    || selector.to_string().eq_ignore_ascii_case(expected)
+            }
+            RevertReason::OutOfGas => expected == "outOfGas",
+        }
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Unknown => write!(f, "without a reason"),
+            RevertReason::Message(message) => write!(f, "with reason \"{}\"", message),
+            RevertReason::CustomError(error) => {
+                let selector = Bytes::from(&error.as_slice()[..4]);
+                write!(f, "with custom error {} ({})", selector, error)
+            }
+            RevertReason::OutOfGas => write!(f, "out of gas"),
+        }
+    }
+}
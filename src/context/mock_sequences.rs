@@ -0,0 +1,109 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use crate::context::contract_calls::ContractCall;
+use crate::context::mock_result::{MockResult, RevertReason};
+
+/// What a mocked function returns once all values of its sequence have been consumed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum WhenExhausted {
+    /// Falls back to the value mocked with `mockFunction` or with argument matchers
+    Fallback,
+    /// Keeps returning the last value of the sequence
+    RepeatLast,
+    /// Starts the sequence again from the first value
+    Cycle,
+    /// Reverts every call
+    Revert,
+    /// Fails the call, even if there is another mock for it
+    Fail,
+}
+
+impl FromStr for WhenExhausted {
+    type Err = anyhow::Error;
+
+    fn from_str(behavior: &str) -> Result<Self, Self::Err> {
+        match behavior {
+            "fallback" => Ok(WhenExhausted::Fallback),
+            "repeatLast" => Ok(WhenExhausted::RepeatLast),
+            "cycle" => Ok(WhenExhausted::Cycle),
+            "revert" => Ok(WhenExhausted::Revert),
+            "fail" => Ok(WhenExhausted::Fail),
+            _ => Err(anyhow!(
+                "Unknown behavior `{}`, expected one of: fallback, repeatLast, cycle, revert, fail",
+                behavior
+            )),
+        }
+    }
+}
+
+/// The next value of a sequence, or what to do instead when the sequence is exhausted.
+#[derive(Debug, PartialEq)]
+pub(crate) enum NextValue {
+    Value(MockResult),
+    Fallback,
+    Fail,
+}
+
+/// An ordered sequence of return values of a mocked function, consumed one value per call.
+#[derive(Clone, Debug)]
+pub(crate) struct MockSequence {
+    pub(crate) call: ContractCall,
+    values: Vec<MockResult>,
+    /// Number of calls answered by the sequence
+    pub(crate) hits: usize,
+    pub(crate) when_exhausted: WhenExhausted,
+}
+
+impl MockSequence {
+    pub(crate) fn new(call: ContractCall) -> Self {
+        MockSequence {
+            call,
+            values: vec![],
+            hits: 0,
+            when_exhausted: WhenExhausted::Fallback,
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: MockResult) {
+        self.values.push(value);
+    }
+
+    /// Consumes the next value of the sequence.
+    pub(crate) fn next_value(&mut self) -> NextValue {
+        let index = self.hits;
+        let value = match self.values.get(index) {
+            Some(value) => Some(value.clone()),
+            None if self.values.is_empty() => None,
+            None => match self.when_exhausted {
+                WhenExhausted::RepeatLast => self.values.last().cloned(),
+                WhenExhausted::Cycle => Some(self.values[index % self.values.len()].clone()),
+                WhenExhausted::Revert => Some(MockResult::Revert(RevertReason::Unknown)),
+                WhenExhausted::Fallback | WhenExhausted::Fail => None,
+            },
+        };
+
+        match value {
+            Some(value) => {
+                self.hits += 1;
+                NextValue::Value(value)
+            }
+            None if self.when_exhausted == WhenExhausted::Fail => NextValue::Fail,
+            None => NextValue::Fallback,
+        }
+    }
+}
+
+impl fmt::Display for MockSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (sequence of {} values, {} consumed)",
+            self.call,
+            self.values.len(),
+            self.hits.min(self.values.len())
+        )
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::str::FromStr;
 
 use anyhow::{anyhow, Context};
@@ -31,22 +34,49 @@ use lazy_static::lazy_static;
 use serde_json::to_string_pretty;
 
 use crate::logging;
-use crate::SCHEMA_LOCATION;
-
+use crate::parser::ManifestDataSource;
+use crate::{MANIFEST_LOCATION, NETWORK, NETWORKS_LOCATION, SCHEMA_LOCATION, UPDATE_SNAPSHOTS};
+
+mod abis;
+mod assertions;
+mod call_matchers;
+mod cassette;
+mod contract_calls;
 mod conversion;
+mod data_sources;
 mod derived_fields;
 mod derived_schema;
-use conversion::{collect_types, get_kind, get_token_value};
+mod entity_id;
+mod fixtures;
+mod graphql;
+mod mock_fixtures;
+mod mock_result;
+mod mock_sequences;
+mod references;
+mod snapshot;
+mod store_operations;
+use abis::ContractAbis;
+use assertions::AssertionMessage;
+use call_matchers::{find_matcher_mock, ArgMatcher, MatcherMock};
+pub(crate) use cassette::Cassette;
+use contract_calls::{parse_contract_address, ContractCall, ExactMock, RecordedCall};
+use conversion::{collect_types, format_typed_value, get_kind, get_token_value};
+use data_sources::{
+    data_source_for_test, format_data_sources, manifest_data_sources, manifest_templates,
+    CreatedDataSource,
+};
 use derived_fields::{
-    cascade_remove, insert_derived_field_in_store, update_derived_relations_in_store,
+    cascade_remove, child_id_value, collect_children, derived_value, insert_derived_field_in_store,
+    update_derived_relations_in_store,
 };
-use derived_schema::derive_schema;
+use derived_schema::{derive_schema, is_single_derived_field};
+use entity_id::{id_key, store_key, IdType};
+use graphql::BlockStore;
+pub(crate) use mock_result::{MockResult, RevertReason};
+use mock_sequences::{MockSequence, NextValue, WhenExhausted};
+use store_operations::{OperationKind, StoreOperation};
 
 lazy_static! {
-    /// Special tokens...
-    pub(crate) static ref REVERTS_IDENTIFIER: Vec<Token> =
-        vec![Token::Bytes(vec![255, 255, 255, 255, 255, 255, 255])];
-
     /// The global GraphQL Schema from `schema.graphql`.
     static ref SCHEMA: schema::Document<'static, String> = {
         let mut s = "".to_owned();
@@ -69,15 +99,106 @@ lazy_static! {
     };
 }
 
+/// The ABIs, templates and data sources declared in the manifest.
+pub(crate) struct Manifest {
+    /// ABIs declared in the manifest, which the mocked functions are validated against
+    abis: ContractAbis,
+    /// The name and network of the templates in the manifest, `None` if there is no manifest
+    templates: Option<Vec<(String, String)>>,
+    /// The data sources from the manifest, which give the default `dataSource` values
+    data_sources: Vec<ManifestDataSource>,
+}
+
+/// The manifest location, the networks file location and the selected network.
+type ManifestKey = (PathBuf, PathBuf, Option<String>);
+
+thread_local! {
+    /// The manifest shared by all instances, including the ones the `ipfs.map` callbacks run in.
+    /// It is only loaded again if one of its locations changes.
+    static MANIFEST: RefCell<Option<(ManifestKey, Rc<Manifest>)>> = RefCell::new(None);
+}
+
+/// Loads the manifest from the configured locations the first time and shares it afterwards.
+fn shared_manifest() -> Rc<Manifest> {
+    let key: ManifestKey = (
+        MANIFEST_LOCATION.with(|path| path.borrow().clone()),
+        NETWORKS_LOCATION.with(|path| path.borrow().clone()),
+        NETWORK.with(|network| network.borrow().clone()),
+    );
+
+    MANIFEST.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if let Some((loaded, manifest)) = cached.as_ref() {
+            if *loaded == key {
+                return manifest.clone();
+            }
+        }
+
+        let (manifest_path, networks_path, network) = &key;
+        let manifest = Rc::new(Manifest {
+            abis: ContractAbis::load(manifest_path),
+            templates: manifest_templates(manifest_path, network.as_deref()),
+            data_sources: manifest_data_sources(manifest_path, networks_path, network.as_deref()),
+        });
+        *cached = Some((key, manifest.clone()));
+        manifest
+    })
+}
+
+/// Finds the definition of an entity type in the global GraphQL Schema.
+pub(crate) fn get_entity_definition(
+    entity_type: &str,
+) -> Option<&'static schema::ObjectType<'static, String>> {
+    SCHEMA.definitions.iter().find_map(|def| match def {
+        schema::Definition::TypeDefinition(schema::TypeDefinition::Object(o))
+            if o.name == entity_type =>
+        {
+            Some(o)
+        }
+        _ => None,
+    })
+}
+
+/// Finds the values of an enum type in the global GraphQL Schema.
+pub(crate) fn get_enum_values(enum_type: &str) -> Option<Vec<&'static str>> {
+    SCHEMA.definitions.iter().find_map(|def| match def {
+        schema::Definition::TypeDefinition(schema::TypeDefinition::Enum(e))
+            if e.name == enum_type =>
+        {
+            Some(e.values.iter().map(|v| v.name.as_str()).collect())
+        }
+        _ => None,
+    })
+}
+
 /// The Matchstick Instance Context wraps WASM Instance Context and
 /// implements the external functions.
 pub struct MatchstickInstanceContext<C: Blockchain> {
     /// Handle to WASM Instance Context.
     pub wasm_ctx: WasmInstanceContext<C>,
     /// Store<EntityType, EntityTypeStore<EntityId, Entity<Field, Value>>>.
+    /// Entities are kept under the string form of their id (lowercase hex for `Bytes` ids),
+    /// while the `id` field of the entity holds the id with its schema type.
     pub(crate) store: HashMap<String, HashMap<String, HashMap<String, Value>>>,
-    /// Function-Return map storing mocked Smart Contracts' functions' return values.
-    pub(crate) fn_ret_map: HashMap<String, Vec<Token>>,
+    /// Function-Return map storing mocked Smart Contracts' functions' return values or reverts.
+    pub(crate) fn_ret_map: HashMap<String, MockResult>,
+    /// Smart Contracts' functions mocked with argument matchers, in the order they were mocked.
+    pub(crate) matcher_mocks: Vec<MatcherMock>,
+    /// The functions mocked with `mockFunction` by their function id, used to report unused mocks.
+    exact_mocks: HashMap<String, ExactMock>,
+    /// Sequences of return values mocked with `mockFunctionOnce` by their function id,
+    /// which take precedence over all other mocks until they are exhausted.
+    fn_sequences: HashMap<String, MockSequence>,
+    /// The `ethereum.call` calls made since the test started
+    pub(crate) contract_calls: Vec<RecordedCall>,
+    /// The ABIs, templates and data sources of the manifest, shared by all instances
+    pub(crate) manifest: Rc<Manifest>,
+    /// Contract calls recorded from a JSON-RPC node, which answer the calls that are not mocked
+    pub(crate) cassette: Option<Cassette>,
+    /// The fixtures from the config, which are loaded into the store again before every test
+    pub(crate) fixtures: Vec<String>,
+    /// The data sources created from templates since the test started
+    pub(crate) created_data_sources: Vec<CreatedDataSource>,
     /// Registered tests metadata.
     pub meta_tests: Vec<(String, bool, u32, String)>,
     /// Holding the derived field type and a tuple of the entity it points to
@@ -106,6 +227,48 @@ pub struct MatchstickInstanceContext<C: Blockchain> {
     /// Holds the mocked ipfs files in a HashMap, where key is the file hash, and the value is the
     /// path to the file that matchstick should read and parse
     pub(crate) ipfs: HashMap<String, String>,
+    /// States saved with `saveStore(name)`, which can be brought back with `restoreStore(name)`
+    saved_states: HashMap<String, SavedState>,
+    /// Path to the test file the instance was compiled from, snapshots are saved next to it
+    test_file: PathBuf,
+    /// The data source from the manifest whose handlers are tested by the test file
+    tested_data_source: Option<ManifestDataSource>,
+    /// Message and call site set with `_assert.withMessage`, consumed by the next assertion
+    pub(crate) assertion_message: Option<AssertionMessage>,
+    /// The `store.set`, `store.remove` and `store.get` calls made since the test started
+    pub(crate) store_operations: Vec<StoreOperation>,
+    /// Block started with `startBlock`, which hasn't been committed yet
+    pending_block: Option<(u32, String)>,
+    /// Versions of the store at the end of every committed block, ordered by block number
+    committed_blocks: Vec<CommittedBlock>,
+    /// The store as it was before the first block was started
+    store_before_blocks: Option<HashMap<String, HashMap<String, HashMap<String, Value>>>>,
+}
+
+/// The state of the store at the end of a simulated block.
+#[derive(Clone)]
+struct CommittedBlock {
+    number: u32,
+    hash: String,
+    store: HashMap<String, HashMap<String, HashMap<String, Value>>>,
+}
+
+/// A copy of the store, the mocked functions and the mocked data source values
+/// saved under a name, e.g. after setting up fixtures in `beforeAll`.
+#[derive(Clone)]
+struct SavedState {
+    store: HashMap<String, HashMap<String, HashMap<String, Value>>>,
+    store_updated: bool,
+    fn_ret_map: HashMap<String, MockResult>,
+    matcher_mocks: Vec<MatcherMock>,
+    exact_mocks: HashMap<String, ExactMock>,
+    fn_sequences: HashMap<String, MockSequence>,
+    data_source_return_value: (
+        Option<String>,
+        Option<String>,
+        Option<HashMap<Attribute, Value>>,
+    ),
+    ipfs: HashMap<String, String>,
 }
 
 /// Implementation of non-external functions.
@@ -115,11 +278,27 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
             wasm_ctx,
             store: HashMap::new(),
             fn_ret_map: HashMap::new(),
+            matcher_mocks: Vec::new(),
+            exact_mocks: HashMap::new(),
+            fn_sequences: HashMap::new(),
+            contract_calls: Vec::new(),
+            manifest: shared_manifest(),
+            cassette: None,
+            fixtures: Vec::new(),
+            created_data_sources: Vec::new(),
             meta_tests: Vec::new(),
             derived: HashMap::new(),
             store_updated: true,
             data_source_return_value: (None, None, None),
             ipfs: HashMap::new(),
+            saved_states: HashMap::new(),
+            test_file: PathBuf::new(),
+            tested_data_source: None,
+            assertion_message: None,
+            store_operations: Vec::new(),
+            pending_block: None,
+            committed_blocks: Vec::new(),
+            store_before_blocks: None,
         };
         derive_schema(&mut context);
         context
@@ -138,6 +317,427 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         }
         unique_fn_string
     }
+
+    /// Extracts the argument types from the function signature,
+    /// e.g "fnName(int32, string, address):(bool)" -> ["int32", "string", "address"].
+    /// Fails if the function name doesn't match the name in the signature.
+    fn signature_arg_types(
+        fn_name: &str,
+        fn_signature: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let fn_signature_split: Vec<&str> = fn_signature.split('(').collect();
+        if fn_name != fn_signature_split[0] {
+            return Err(anyhow!(
+                "createMockedFunction: function name `{}` should match the name in the function signature `{}`",
+                fn_name,
+                fn_signature
+            ));
+        }
+
+        let tmp_str = fn_signature.replace(&(fn_name.to_owned() + "("), "");
+        let components: Vec<&str> = tmp_str.split("):").collect();
+        Ok(collect_types(components[0]))
+    }
+
+    /// Reads the mocked contract call passed to `mockFunction`, `mockFunctionOnce`
+    /// or `mockFunctionRevert` and validates the arguments against the function signature.
+    /// The signature is checked against the ABIs from the manifest, or derived from them if empty.
+    fn read_mocked_call(
+        &self,
+        contract_address_ptr: u32,
+        fn_name_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+    ) -> Result<ContractCall, HostExportError> {
+        let contract_address: Address = asc_get(
+            &self.wasm_ctx,
+            contract_address_ptr.into(),
+            &GasCounter::new(),
+        )?;
+        let fn_name: String = asc_get(&self.wasm_ctx, fn_name_ptr, &GasCounter::new())?;
+        let fn_signature: String = asc_get(&self.wasm_ctx, fn_signature_ptr, &GasCounter::new())?;
+        let fn_args: Vec<Token> = asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
+            &self.wasm_ctx,
+            fn_args_ptr.into(),
+            &GasCounter::new(),
+        )?;
+        let fn_signature = self
+            .manifest
+            .abis
+            .resolve_signature(&fn_name, &fn_signature, &fn_args)
+            .map_err(|err| anyhow!("createMockedFunction: {}", err))?;
+
+        let arg_types =
+            MatchstickInstanceContext::<C>::signature_arg_types(&fn_name, &fn_signature)?;
+
+        // Checks if the count of the passed arguments matches the count of expected arguments
+        if arg_types.len() != fn_args.len() {
+            return Err(anyhow!(
+                "{} expected {} arguments, but received {}",
+                fn_name,
+                arg_types.len(),
+                fn_args.len()
+            )
+            .into());
+        }
+
+        // Validates that every passed argument matches the type of the expected argument
+        // from the function signature. Panics if there is a mismatch and informs the user
+        // of the position and the expected and recieved type
+        for (index, (arg_type, fn_arg)) in arg_types.iter().zip(fn_args.iter()).enumerate() {
+            let param_type = get_kind(arg_type.to_owned());
+
+            if !fn_arg.type_check(&param_type) {
+                return Err(anyhow!(
+                    "createMockedFunction `{}` parameters mismatch at position {}:\nExpected: {:?}\nRecieved: {:?}\n",
+                    fn_name,
+                    index + 1,
+                    param_type,
+                    fn_arg
+                ).into());
+            }
+        }
+
+        Ok(ContractCall {
+            contract_address,
+            fn_name,
+            fn_signature,
+            args: fn_args,
+        })
+    }
+
+    /// Reads the mocked return value, functions mocked with `reverts`
+    /// revert without a reason instead.
+    fn read_mocked_result(
+        &self,
+        return_value_ptr: u32,
+        reverts_ptr: AscPtr<bool>,
+    ) -> Result<MockResult, HostExportError> {
+        let return_value: Vec<Token> = asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
+            &self.wasm_ctx,
+            return_value_ptr.into(),
+            &GasCounter::new(),
+        )?;
+        let reverts = bool::from(EnumPayload(reverts_ptr.to_payload()));
+
+        if reverts {
+            Ok(MockResult::Revert(RevertReason::Unknown))
+        } else {
+            Ok(MockResult::Return(return_value))
+        }
+    }
+
+    /// The key of a mocked function in the function-return map.
+    fn call_fn_id(call: &ContractCall) -> String {
+        MatchstickInstanceContext::<C>::fn_id(
+            &call.contract_address.to_string(),
+            &call.fn_name,
+            &call.fn_signature,
+            &call.args,
+        )
+    }
+
+    /// Validates the entity data against the schema and saves it in the store,
+    /// linking it to the entities it's derived from.
+    pub(crate) fn store_set(
+        &mut self,
+        entity_type: String,
+        id: String,
+        mut data: HashMap<String, Value>,
+    ) -> Result<(), anyhow::Error> {
+        let id_type = IdType::of(&entity_type);
+        let id_value = id_type.parse(&id).map_err(|err| {
+            anyhow!(
+                "Invalid id '{}' for an entity of type '{}' with `{}` id: {}",
+                id,
+                entity_type,
+                id_type,
+                err
+            )
+        })?;
+
+        match data.get("id") {
+            None => {
+                data.insert("id".to_owned(), id_value.clone());
+            }
+            Some(Value::Null) => {}
+            Some(data_id) if !id_type.matches(data_id) => {
+                return Err(anyhow!(
+                    "The id field of an entity of type '{}' should be of type `{}`, but was '{}'.",
+                    entity_type,
+                    id_type,
+                    data_id
+                ));
+            }
+            Some(data_id) if id_key(data_id) != id_key(&id_value) => {
+                return Err(anyhow!(
+                    "The id field '{}' of an entity of type '{}' does not match the id '{}' it is saved with.",
+                    data_id,
+                    entity_type,
+                    id
+                ));
+            }
+            Some(_) => {}
+        }
+
+        let id = id_key(&id_value).unwrap();
+
+        let required_fields = get_entity_definition(&entity_type)
+            .unwrap_or_else(|| {
+                logging::critical!("Something went wrong! Could not find the entity defined in the GraphQL schema.")
+            })
+            .fields
+            .iter()
+            .filter(|&f| matches!(f.field_type, schema::Type::NonNullType(..)) && !f.is_derived());
+
+        for f in required_fields {
+            if !data.contains_key(&f.name) {
+                return Err(anyhow!(
+                    "Missing value for non-nullable field '{}' for an entity of type '{}'.",
+                    f.name,
+                    entity_type,
+                ));
+            } else if let Value::Null = data.get(&f.name).unwrap() {
+                return Err(anyhow!(
+                    "The required field '{}' for an entity of type '{}' is null.",
+                    f.name,
+                    entity_type,
+                ));
+            }
+        }
+
+        if self.derived.contains_key(&entity_type) {
+            let linking_fields = self
+                .derived
+                .get(&entity_type)
+                .unwrap_or_else(|| {
+                    logging::critical!("Couldn't find value for key {} in derived map", entity_type)
+                })
+                .clone();
+            let child_id = child_id_value(&id, &data);
+            for linking_field in linking_fields {
+                if let Some(derived_field_value) = data.get(&linking_field.1) {
+                    let original_entity_type = linking_field.2.clone();
+                    insert_derived_field_in_store(
+                        self,
+                        derived_field_value.clone(),
+                        original_entity_type,
+                        linking_field.clone(),
+                        child_id.clone(),
+                    );
+                }
+            }
+        }
+
+        let mut entity_type_store = if self.store.contains_key(&entity_type) {
+            self.store.get(&entity_type).unwrap().clone()
+        } else {
+            HashMap::new()
+        };
+
+        // Collect all child entities for the passed entity_type
+        let child_entities: HashMap<String, Vec<(String, String, String)>> = self
+            .derived
+            .iter()
+            .filter_map(|(linked_entity, linking_fields)| {
+                let mapping: Vec<(String, String, String)> = linking_fields
+                    .iter()
+                    .filter(|linking_field| linking_field.2 == entity_type)
+                    .cloned()
+                    .collect();
+
+                if mapping.is_empty() {
+                    None
+                } else {
+                    Some((linked_entity.clone(), mapping))
+                }
+            })
+            .collect();
+
+        // Iterate over all child entities
+        // Fetch all saved records
+        // Collect the ids of the records which derivedFrom field points to the passed entity id
+        // Update the parent's data with the child record (one-to-one) or the list of child records
+        for (linked_entity, linking_fields) in child_entities.iter() {
+            if self.store.contains_key(linked_entity) {
+                for linking_field in linking_fields.iter() {
+                    let children =
+                        collect_children(&self.store, linked_entity, &linking_field.1, &id, None);
+                    let single = is_single_derived_field(&entity_type, &linking_field.0);
+
+                    data.insert(linking_field.0.clone(), derived_value(children, single));
+                }
+            }
+        }
+
+        entity_type_store.insert(id, data);
+        self.store.insert(entity_type, entity_type_store);
+        self.store_updated = false;
+        Ok(())
+    }
+
+    /// Loads the entities from a JSON or YAML fixture file into the store.
+    pub(crate) fn load_fixture(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        fixtures::load_fixture(self, path)
+    }
+
+    /// Loads the contract calls mocked in a JSON or YAML fixture file.
+    pub(crate) fn load_mock_fixture(&mut self, path: &str) -> Result<(), anyhow::Error> {
+        mock_fixtures::load_mock_fixture(self, path)
+    }
+
+    /// Runs a GraphQL query against the store and returns the response as it would
+    /// come from the subgraph's API, with either `data` or `errors`.
+    /// `variables` is a JSON object, an empty string means no variables.
+    pub(crate) fn query_store(&mut self, query: &str, variables: &str) -> serde_json::Value {
+        update_derived_relations_in_store(self);
+
+        let variables = if variables.trim().is_empty() {
+            Ok(serde_json::Map::new())
+        } else {
+            serde_json::from_str(variables)
+                .map_err(|err| anyhow!("Variables must be a JSON object: {}", err))
+        };
+
+        let mut blocks: Vec<BlockStore> = self
+            .committed_blocks
+            .iter()
+            .map(|block| BlockStore {
+                number: block.number,
+                hash: &block.hash,
+                store: &block.store,
+            })
+            .collect();
+        // Blocks before the first committed block are queried against the store
+        // as it was before the first block was started
+        if let (Some(first), Some(store)) = (blocks.first(), &self.store_before_blocks) {
+            if first.number > 0 {
+                blocks.insert(
+                    0,
+                    BlockStore {
+                        number: 0,
+                        hash: "",
+                        store,
+                    },
+                );
+            }
+        }
+
+        match variables
+            .and_then(|variables| graphql::execute_query(&self.store, &blocks, query, &variables))
+        {
+            Ok(data) => serde_json::json!({ "data": data }),
+            Err(err) => serde_json::json!({ "errors": [{ "message": format!("{:#}", err) }] }),
+        }
+    }
+
+    /// Formats the store operations recorded since the start of the test.
+    pub(crate) fn format_store_operations(&self) -> String {
+        store_operations::format_operations(&self.store_operations)
+    }
+
+    /// Writes the store as JSON with typed values to `path`,
+    /// optionally keeping only the entities of one type and/or with ids starting with a prefix.
+    pub(crate) fn write_store(
+        &mut self,
+        path: &Path,
+        entity_type: Option<&str>,
+        id_prefix: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        update_derived_relations_in_store(self);
+        let store = snapshot::filter_store(&self.store, entity_type, id_prefix);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, snapshot::serialize_store(&store))
+            .with_context(|| format!("Could not write the store to `{}`", path.display()))
+    }
+
+    /// Dumps the store of a failed test to
+    /// `<artifacts folder>/<test file name>/<test name>.json` and returns the file location.
+    pub(crate) fn dump_failed_test(
+        &mut self,
+        artifacts: &Path,
+        test_name: &str,
+    ) -> Result<PathBuf, anyhow::Error> {
+        let path = artifacts
+            .join(snapshot::test_file_name(&self.test_file))
+            .join(snapshot::sanitize_file_name(test_name))
+            .with_extension("json");
+
+        self.write_store(&path, None, None)?;
+        Ok(path)
+    }
+
+    /// Describes the mocked functions which haven't been called since they were mocked,
+    /// the mocks with exact arguments and the sequences first, sorted,
+    /// then the mocks with argument matchers.
+    pub(crate) fn unused_mocks(&self) -> Vec<String> {
+        let mut unused: Vec<String> = self
+            .exact_mocks
+            .values()
+            .filter(|mock| mock.hits == 0)
+            .map(|mock| mock.call.to_string())
+            .collect();
+        unused.extend(
+            self.fn_sequences
+                .values()
+                .filter(|sequence| sequence.hits == 0)
+                .map(|sequence| sequence.to_string()),
+        );
+        unused.sort();
+        unused.extend(
+            self.matcher_mocks
+                .iter()
+                .filter(|mock| mock.hits == 0)
+                .map(|mock| mock.to_string()),
+        );
+        unused
+    }
+
+    /// Formats the contract calls recorded since the start of the test.
+    pub(crate) fn format_contract_calls(&self) -> String {
+        contract_calls::format_calls(&self.contract_calls)
+    }
+
+    /// Formats the data sources created from templates since the start of the test.
+    pub(crate) fn format_data_sources(&self) -> String {
+        format_data_sources(&self.created_data_sources)
+    }
+
+    /// Records a data source created from a template,
+    /// failing if the template is not declared in the manifest.
+    fn create_data_source(
+        &mut self,
+        template: String,
+        params: Vec<String>,
+        context: Option<BTreeMap<String, Value>>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(templates) = &self.manifest.templates {
+            if !templates.iter().any(|(name, _)| *name == template) {
+                let names: Vec<&str> = templates.iter().map(|(name, _)| name.as_str()).collect();
+                return Err(anyhow!(
+                    "Template `{}` is not declared in the manifest, expected one of: {}",
+                    template,
+                    names.join(", ")
+                ));
+            }
+        }
+
+        self.created_data_sources.push(CreatedDataSource {
+            template,
+            params,
+            context,
+        });
+        Ok(())
+    }
+
+    /// Collects the entity references in the store which point to entities that don't exist.
+    pub(crate) fn dangling_references(&mut self) -> Vec<references::DanglingReference> {
+        update_derived_relations_in_store(self);
+        references::dangling_references(&self.store)
+    }
 }
 
 /// Implementation of external functions (used in AssemblyScript sources).
@@ -161,22 +761,495 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
 
     /// function logStore(): void
     pub fn log_store(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        let store: BTreeMap<&String, BTreeMap<&String, BTreeMap<&String, &Value>>> = self
+            .store
+            .iter()
+            .map(|(entity_type, entities)| {
+                let entities = entities
+                    .iter()
+                    .map(|(id, entity)| (id, entity.iter().collect()))
+                    .collect();
+                (entity_type, entities)
+            })
+            .collect();
+
         logging::debug!(
             "{}",
-            to_string_pretty(&self.store).unwrap_or_else(|err| logging::critical!(err)),
+            to_string_pretty(&store).unwrap_or_else(|err| logging::critical!(err)),
         );
         Ok(())
     }
 
-    /// function clearStore(): void
-    pub fn clear_store(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
-        self.store.clear();
-        self.store_updated = true;
+    /// function logStoreOperations(): void
+    pub fn log_store_operations(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        logging::debug!(
+            "{}",
+            store_operations::format_operations(&self.store_operations)
+        );
         Ok(())
     }
 
-    /// function _registerTest(name: string, shouldFail: bool, funcIdx: u32): void
-    pub fn register_test(
+    /// function clearStoreOperations(): void
+    pub fn clear_store_operations(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        self.store_operations.clear();
+        Ok(())
+    }
+
+    /// function _assert.storeOperationCount(operation: string, entityType: string, id: string, expectedCount: u32): bool
+    /// `operation` is one of `set`, `remove` or `get`, an empty `id` counts the operations on all entities of the type.
+    pub fn assert_store_operation_count(
+        &mut self,
+        _gas: &GasCounter,
+        operation_ptr: AscPtr<AscString>,
+        entity_type_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+        expected_count: u32,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let operation: String = asc_get(&self.wasm_ctx, operation_ptr, &GasCounter::new())?;
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let kind: OperationKind = operation
+            .parse()
+            .map_err(|err| anyhow!("(assert.storeOperationCount) {}", err))?;
+        let id = if id.is_empty() {
+            id
+        } else {
+            store_key(&entity_type, &id)
+        };
+
+        let count = self
+            .store_operations
+            .iter()
+            .filter(|operation| operation.matches(kind, &entity_type, &id))
+            .count() as u32;
+
+        if count != expected_count {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.storeOperationCount) Expected {} to be called {} times for entity type '{}'{}, but it was called {} times. Recorded operations:\n{}",
+                    kind,
+                    expected_count,
+                    &entity_type,
+                    if id.is_empty() { String::new() } else { format!(" and id '{}'", id) },
+                    count,
+                    store_operations::format_operations(&self.store_operations)
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.noDanglingReferences(): bool
+    /// Checks that every entity reference in the store points to an existing entity.
+    pub fn assert_no_dangling_references(
+        &mut self,
+        _gas: &GasCounter,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let dangling = self.dangling_references();
+
+        if !dangling.is_empty() {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.noDanglingReferences) Found {} dangling references:\n{}",
+                    dangling.len(),
+                    dangling
+                        .iter()
+                        .map(|reference| reference.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function clearStore(): void
+    pub fn clear_store(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        self.store.clear();
+        self.store_updated = true;
+        self.clear_blocks();
+        Ok(())
+    }
+
+    /// function loadFixture(path: string): void
+    pub fn load_fixture_file(
+        &mut self,
+        _gas: &GasCounter,
+        path_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let path: String = asc_get(&self.wasm_ctx, path_ptr, &GasCounter::new())?;
+
+        self.load_fixture(&path)
+            .map_err(|err| anyhow!("(loadFixture) {:#}", err))?;
+        Ok(())
+    }
+
+    /// function loadMockFixture(path: string): void
+    pub fn load_mock_fixture_file(
+        &mut self,
+        _gas: &GasCounter,
+        path_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let path: String = asc_get(&self.wasm_ctx, path_ptr, &GasCounter::new())?;
+
+        self.load_mock_fixture(&path)
+            .map_err(|err| anyhow!("(loadMockFixture) {:#}", err))?;
+        Ok(())
+    }
+
+    /// function dumpStore(path: string, entityType: string, idPrefix: string): void
+    /// Empty `entityType` and `idPrefix` dump the whole store.
+    pub fn dump_store(
+        &mut self,
+        _gas: &GasCounter,
+        path_ptr: AscPtr<AscString>,
+        entity_type_ptr: AscPtr<AscString>,
+        id_prefix_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let path: String = asc_get(&self.wasm_ctx, path_ptr, &GasCounter::new())?;
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id_prefix: String = asc_get(&self.wasm_ctx, id_prefix_ptr, &GasCounter::new())?;
+
+        self.write_store(
+            Path::new(&path),
+            Some(entity_type.as_str()).filter(|t| !t.is_empty()),
+            Some(id_prefix.as_str()).filter(|p| !p.is_empty()),
+        )
+        .map_err(|err| anyhow!("(dumpStore) {:#}", err))?;
+        Ok(())
+    }
+
+    /// function graphqlQuery(query: string, variables: string): string
+    pub fn graphql_query(
+        &mut self,
+        _gas: &GasCounter,
+        query_ptr: AscPtr<AscString>,
+        variables_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<AscString>, HostExportError> {
+        let query: String = asc_get(&self.wasm_ctx, query_ptr, &GasCounter::new())?;
+        let variables: String = asc_get(&self.wasm_ctx, variables_ptr, &GasCounter::new())?;
+
+        let response = self.query_store(&query, &variables);
+        let result = AscPtr::alloc_obj(
+            asc_string_from_str(&response.to_string()),
+            &mut self.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+
+        Ok(result)
+    }
+
+    /// function _assert.queryResult(query: string, variables: string, expected: string): bool
+    /// `expected` is the JSON response, e.g. `{"data": {"gravatars": [{"id": "1"}]}}`.
+    pub fn assert_query_result(
+        &mut self,
+        _gas: &GasCounter,
+        query_ptr: AscPtr<AscString>,
+        variables_ptr: AscPtr<AscString>,
+        expected_ptr: AscPtr<AscString>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let query: String = asc_get(&self.wasm_ctx, query_ptr, &GasCounter::new())?;
+        let variables: String = asc_get(&self.wasm_ctx, variables_ptr, &GasCounter::new())?;
+        let expected: String = asc_get(&self.wasm_ctx, expected_ptr, &GasCounter::new())?;
+        let expected: serde_json::Value = serde_json::from_str(&expected).map_err(|err| {
+            anyhow!(
+                "(assert.queryResult) Expected result is not valid JSON: {}",
+                err
+            )
+        })?;
+
+        let actual = self.query_store(&query, &variables);
+        if actual != expected {
+            let pretty = |value: &serde_json::Value| {
+                to_string_pretty(value).unwrap_or_else(|err| logging::critical!(err))
+            };
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.queryResult) Query result does not match the expected one:\n{}",
+                    snapshot::line_diff(&pretty(&expected), &pretty(&actual))
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.storeMatchesSnapshot(name: string): bool
+    pub fn assert_store_matches_snapshot(
+        &mut self,
+        _gas: &GasCounter,
+        name_ptr: AscPtr<AscString>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        update_derived_relations_in_store(self);
+        let name: String = asc_get(&self.wasm_ctx, name_ptr, &GasCounter::new())?;
+
+        let path = snapshot::snapshot_path(&self.test_file, &name);
+        let actual = snapshot::serialize_store(&self.store);
+        let update = UPDATE_SNAPSHOTS.with(|update| *update.borrow());
+
+        if update || !path.exists() {
+            std::fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| std::fs::write(&path, &actual))
+                .map_err(|err| {
+                    anyhow!(
+                        "(assert.storeMatchesSnapshot) Could not write snapshot `{}`: {}",
+                        path.display(),
+                        err
+                    )
+                })?;
+            logging::info!("Snapshot `{}` written to {}", name, path.display());
+
+            return Ok(true);
+        }
+
+        let expected = std::fs::read_to_string(&path).map_err(|err| {
+            anyhow!(
+                "(assert.storeMatchesSnapshot) Could not read snapshot `{}`: {}",
+                path.display(),
+                err
+            )
+        })?;
+
+        if expected != actual {
+            assertions::log_failure(&message, format!(
+                "(assert.storeMatchesSnapshot) Store does not match snapshot '{}' ({}). Run with --update-snapshots to update it.\n{}",
+                name,
+                path.display(),
+                snapshot::line_diff(&expected, &actual)
+            ));
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function saveStore(name: string): void
+    pub fn save_store(
+        &mut self,
+        _gas: &GasCounter,
+        name_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let name: String = asc_get(&self.wasm_ctx, name_ptr, &GasCounter::new())?;
+
+        self.saved_states.insert(
+            name,
+            SavedState {
+                store: self.store.clone(),
+                store_updated: self.store_updated,
+                fn_ret_map: self.fn_ret_map.clone(),
+                matcher_mocks: self.matcher_mocks.clone(),
+                exact_mocks: self.exact_mocks.clone(),
+                fn_sequences: self.fn_sequences.clone(),
+                data_source_return_value: self.data_source_return_value.clone(),
+                ipfs: self.ipfs.clone(),
+            },
+        );
+        Ok(())
+    }
+
+    /// function restoreStore(name: string): void
+    pub fn restore_store(
+        &mut self,
+        _gas: &GasCounter,
+        name_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let name: String = asc_get(&self.wasm_ctx, name_ptr, &GasCounter::new())?;
+
+        let state = self.saved_states.get(&name).cloned().ok_or_else(|| {
+            anyhow!(
+                "(restoreStore) No store state has been saved under the name '{}'.",
+                name
+            )
+        })?;
+
+        self.store = state.store;
+        self.store_updated = state.store_updated;
+        self.fn_ret_map = state.fn_ret_map;
+        self.matcher_mocks = state.matcher_mocks;
+        self.exact_mocks = state.exact_mocks;
+        self.fn_sequences = state.fn_sequences;
+        self.data_source_return_value = state.data_source_return_value;
+        self.ipfs = state.ipfs;
+        Ok(())
+    }
+
+    /// Sets the test file the instance was compiled from
+    /// and finds the data source from the manifest which it tests.
+    pub(crate) fn set_test_file(&mut self, test_file: PathBuf) {
+        self.tested_data_source =
+            data_source_for_test(&self.manifest.data_sources, &test_file).cloned();
+        self.test_file = test_file;
+    }
+
+    /// Drops the started and the committed blocks, the next test can start from any block.
+    pub(crate) fn clear_blocks(&mut self) {
+        self.pending_block = None;
+        self.committed_blocks.clear();
+        self.store_before_blocks = None;
+    }
+
+    /// function startBlock(number: u32, hash: string): void
+    /// The block number has to be greater than the number of the last committed block.
+    pub fn start_block(
+        &mut self,
+        _gas: &GasCounter,
+        number: u32,
+        hash_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let hash: String = asc_get(&self.wasm_ctx, hash_ptr, &GasCounter::new())?;
+
+        if let Some((pending, _)) = &self.pending_block {
+            return Err(anyhow!(
+                "(startBlock) Block {} has been started, but not committed.",
+                pending
+            )
+            .into());
+        }
+
+        if let Some(last) = self.committed_blocks.last() {
+            if number <= last.number {
+                return Err(anyhow!(
+                    "(startBlock) Block number {} must be greater than the last committed block {}.",
+                    number,
+                    last.number
+                )
+                .into());
+            }
+        }
+
+        if self.committed_blocks.is_empty() {
+            self.store_before_blocks = Some(self.store.clone());
+        }
+        self.pending_block = Some((number, hash));
+        Ok(())
+    }
+
+    /// function commitBlock(): void
+    pub fn commit_block(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        update_derived_relations_in_store(self);
+        let (number, hash) = self
+            .pending_block
+            .take()
+            .ok_or_else(|| anyhow!("(commitBlock) No block has been started."))?;
+
+        self.committed_blocks.push(CommittedBlock {
+            number,
+            hash,
+            store: self.store.clone(),
+        });
+        Ok(())
+    }
+
+    /// function revertToBlock(number: u32): void
+    /// Brings back the store as it was at the end of the block,
+    /// dropping all blocks after it together with the block in progress.
+    /// Reverting to a block before the first started block brings back the store
+    /// as it was before that block was started.
+    pub fn revert_to_block(
+        &mut self,
+        _gas: &GasCounter,
+        number: u32,
+    ) -> Result<(), HostExportError> {
+        let position = self
+            .committed_blocks
+            .iter()
+            .position(|block| block.number == number);
+        let first_block = self
+            .committed_blocks
+            .first()
+            .map(|block| block.number)
+            .or_else(|| self.pending_block.as_ref().map(|(number, _)| *number));
+
+        match (position, &self.store_before_blocks, first_block) {
+            (Some(position), _, _) => {
+                self.committed_blocks.truncate(position + 1);
+                self.store = self.committed_blocks[position].store.clone();
+            }
+            (None, Some(store), Some(first_block)) if number < first_block => {
+                self.committed_blocks.clear();
+                self.store = store.clone();
+            }
+            _ => {
+                return Err(
+                    anyhow!("(revertToBlock) Block {} has not been committed.", number).into(),
+                )
+            }
+        }
+        self.pending_block = None;
+        self.store_updated = true;
+        Ok(())
+    }
+
+    /// function storeGetAtBlock(entityType: string, id: string, number: u32): Entity
+    /// Returns the entity as it was at the end of a committed block, or null if it didn't exist.
+    pub fn store_get_at_block(
+        &mut self,
+        _gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+        number: u32,
+    ) -> Result<AscPtr<AscEntity>, HostExportError> {
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
+
+        let block = self
+            .committed_blocks
+            .iter()
+            .find(|block| block.number == number)
+            .ok_or_else(|| anyhow!("(storeGetAtBlock) Block {} has not been committed.", number))?;
+
+        match block
+            .store
+            .get(&entity_type)
+            .and_then(|entities| entities.get(&id))
+        {
+            Some(entity) => {
+                let entity = Entity::from(entity.clone());
+                let res = asc_new(&mut self.wasm_ctx, &entity.sorted(), &GasCounter::new())?;
+                Ok(res)
+            }
+            None => Ok(AscPtr::null()),
+        }
+    }
+
+    /// function blockHash(number: u32): string
+    pub fn block_hash(
+        &mut self,
+        _gas: &GasCounter,
+        number: u32,
+    ) -> Result<AscPtr<AscString>, HostExportError> {
+        let hash = self
+            .committed_blocks
+            .iter()
+            .find(|block| block.number == number)
+            .map(|block| block.hash.clone())
+            .ok_or_else(|| anyhow!("(blockHash) Block {} has not been committed.", number))?;
+
+        let result = AscPtr::alloc_obj(
+            asc_string_from_str(&hash),
+            &mut self.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        Ok(result)
+    }
+
+    /// function _registerTest(name: string, shouldFail: bool, funcIdx: u32): void
+    pub fn register_test(
         &mut self,
         _gas: &GasCounter,
         name: AscPtr<AscString>,
@@ -217,6 +1290,21 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         Ok(())
     }
 
+    /// function _assert.withMessage(message: string, callSite: string): void
+    /// The message and call site are shown if the next assertion fails.
+    pub fn set_assertion_message(
+        &mut self,
+        _gas: &GasCounter,
+        message_ptr: AscPtr<AscString>,
+        call_site_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let message: String = asc_get(&self.wasm_ctx, message_ptr, &GasCounter::new())?;
+        let call_site: String = asc_get(&self.wasm_ctx, call_site_ptr, &GasCounter::new())?;
+
+        self.assertion_message = Some(AssertionMessage { message, call_site });
+        Ok(())
+    }
+
     /// function _assert.fieldEquals(
     ///     entityType: string, id: string,
     ///     fieldName: string, expectedVal: string,
@@ -229,16 +1317,21 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         field_name_ptr: AscPtr<AscString>,
         expected_val_ptr: AscPtr<AscString>,
     ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
         update_derived_relations_in_store(self);
         let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
         let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
         let field_name: String = asc_get(&self.wasm_ctx, field_name_ptr, &GasCounter::new())?;
         let expected_val: String = asc_get(&self.wasm_ctx, expected_val_ptr, &GasCounter::new())?;
 
         if !self.store.contains_key(&entity_type) {
-            logging::error!(
-                "(assert.fieldEquals) No entities with type '{}' found.",
-                &entity_type
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.fieldEquals) No entities with type '{}' found.",
+                    &entity_type
+                ),
             );
 
             return Ok(false);
@@ -246,10 +1339,12 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
 
         let entities = self.store.get(&entity_type).unwrap();
         if !entities.contains_key(&id) {
-            logging::error!(
-                "(assert.fieldEquals) No entity with type '{}' and id '{}' found.",
-                &entity_type,
-                &id
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.fieldEquals) No entity with type '{}' and id '{}' found.",
+                    &entity_type, &id
+                ),
             );
 
             return Ok(false);
@@ -257,23 +1352,39 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
 
         let entity = entities.get(&id).unwrap();
         if !entity.contains_key(&field_name) {
-            logging::error!(
+            assertions::log_failure(&message, format!(
                 "(assert.fieldEquals) No field named '{}' on entity with type '{}' and id '{}' found.",
                 &field_name,
                 &entity_type,
                 &id
-            );
+            ));
 
             return Ok(false);
         }
 
         let val = entity.get(&field_name).unwrap();
-        if val.to_string() != expected_val {
-            logging::error!(
-                "(assert.fieldEquals) Expected field '{}' to equal '{}', but was '{}' instead.",
-                &field_name,
-                &expected_val,
-                val
+        // The expected value is parsed according to the type of the field in the schema,
+        // falling back to comparing the string representations for untyped fields.
+        let expected = get_entity_definition(&entity_type)
+            .and_then(|d| d.fields.iter().find(|f| f.name == field_name))
+            .and_then(|f| assertions::parse_expected_value(&expected_val, &f.field_type));
+        let equal = match &expected {
+            Some(expected) => assertions::values_equal(expected, val),
+            None => false,
+        };
+
+        if !equal && val.to_string() != expected_val {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.fieldEquals) Expected field '{}' to equal '{}', but was '{}' instead.",
+                    &field_name,
+                    expected
+                        .as_ref()
+                        .map(format_typed_value)
+                        .unwrap_or_else(|| expected_val.clone()),
+                    format_typed_value(val)
+                ),
             );
             return Ok(false);
         };
@@ -288,6 +1399,7 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         expected_ptr: u32,
         actual_ptr: u32,
     ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
         update_derived_relations_in_store(self);
         let expected: Token = asc_get::<_, AscEnum<EthereumValueKind>, _>(
             &self.wasm_ctx,
@@ -304,10 +1416,12 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         let act_val = get_token_value(actual);
 
         if exp_val != act_val {
-            logging::error!(
-                "(assert.equals) Expected value was '{}' but actual value was '{}'",
-                exp_val,
-                act_val
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.equals) Expected value was '{}' but actual value was '{}'",
+                    exp_val, act_val
+                ),
             );
             return Ok(false);
         }
@@ -315,209 +1429,274 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         Ok(true)
     }
 
-    /// function _assert.notInStore(entityType: string, id: string): bool
-    pub fn assert_not_in_store(
+    /// function _assert.entityEquals(entityType: string, id: string, expected: Entity): bool
+    /// The `id` and the derived fields are compared only if they are set on the expected entity.
+    pub fn assert_entity_equals(
         &mut self,
         _gas: &GasCounter,
         entity_type_ptr: AscPtr<AscString>,
         id_ptr: AscPtr<AscString>,
+        expected_ptr: AscPtr<AscEntity>,
     ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
         update_derived_relations_in_store(self);
         let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
         let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
-
-        if self.store.contains_key(&entity_type)
-            && self.store.get(&entity_type).unwrap().contains_key(&id)
+        let id = store_key(&entity_type, &id);
+        let expected: HashMap<String, Value> =
+            try_asc_get(&self.wasm_ctx, expected_ptr, &GasCounter::new())?;
+
+        let entity = match self
+            .store
+            .get(&entity_type)
+            .and_then(|entities| entities.get(&id))
         {
-            logging::error!(
-                "(assert.notInStore) Value for entity type: '{}' and id: '{}' was found in store.",
-                entity_type,
-                id
-            );
+            Some(entity) => entity,
+            None => {
+                assertions::log_failure(
+                    &message,
+                    format!(
+                        "(assert.entityEquals) No entity with type '{}' and id '{}' found.",
+                        &entity_type, &id
+                    ),
+                );
+                return Ok(false);
+            }
+        };
+
+        let definition = get_entity_definition(&entity_type);
+        let diff = assertions::entity_diff(&expected, entity, |field| {
+            field == "id"
+                || definition
+                    .and_then(|d| d.fields.iter().find(|f| f.name == field))
+                    .map_or(false, |f| f.is_derived())
+        });
+
+        if !diff.is_empty() {
+            assertions::log_failure(&message, format!(
+                "(assert.entityEquals) Entity with type '{}' and id '{}' differs from the expected one:\n{}",
+                &entity_type,
+                &id,
+                diff.join("\n")
+            ));
             return Ok(false);
         }
 
         Ok(true)
     }
 
-    /// function store.get(entityType: string, id: string): Entity
-    pub fn mock_store_get(
+    /// function _assert.entityCount(entityType: string, expectedCount: u32): bool
+    pub fn assert_entity_count(
         &mut self,
         _gas: &GasCounter,
         entity_type_ptr: AscPtr<AscString>,
-        id_ptr: AscPtr<AscString>,
-    ) -> Result<AscPtr<AscEntity>, HostExportError> {
-        update_derived_relations_in_store(self);
+        expected_count: u32,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
         let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
-        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
-
-        if self.store.contains_key(&entity_type)
-            && self.store.get(&entity_type).unwrap().contains_key(&id)
-        {
-            let entities = self.store.get(&entity_type).unwrap();
-            let entity = entities.get(&id).unwrap().clone();
-            let entity = Entity::from(entity);
 
-            let res = asc_new(&mut self.wasm_ctx, &entity.sorted(), &GasCounter::new())?;
-            return Ok(res);
+        let mut ids: Vec<&String> = self
+            .store
+            .get(&entity_type)
+            .map(|entities| entities.keys().collect())
+            .unwrap_or_default();
+
+        if ids.len() as u32 != expected_count {
+            ids.sort();
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.entityCount) Expected {} entities with type '{}', but found {}: [{}]",
+                    expected_count,
+                    &entity_type,
+                    ids.len(),
+                    ids.iter()
+                        .map(|id| id.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ),
+            );
+            return Ok(false);
         }
 
-        Ok(AscPtr::null())
+        Ok(true)
     }
 
-    /// function store.set(entityType: string, id: string, data: map): void
-    pub fn mock_store_set(
+    /// function _assert.entityExists(entityType: string, id: string): bool
+    pub fn assert_entity_exists(
         &mut self,
         _gas: &GasCounter,
         entity_type_ptr: AscPtr<AscString>,
         id_ptr: AscPtr<AscString>,
-        data_ptr: AscPtr<AscEntity>,
-    ) -> Result<(), HostExportError> {
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
         let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
         let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
-        let mut data: HashMap<String, Value> =
-            try_asc_get(&self.wasm_ctx, data_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
+
+        let entities = self.store.get(&entity_type);
+        if !entities.map_or(false, |entities| entities.contains_key(&id)) {
+            let closest: Vec<&str> = entities
+                .map(|entities| assertions::closest_ids(entities.keys(), &id))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|id| id.as_str())
+                .collect();
+
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.entityExists) No entity with type '{}' and id '{}' found. Closest ids: [{}]",
+                    &entity_type,
+                    &id,
+                    closest.join(", ")
+                ),
+            );
+            return Ok(false);
+        }
 
-        let required_fields = SCHEMA
-        .definitions
-        .iter()
-        .find_map(|def| {
-            if let schema::Definition::TypeDefinition(schema::TypeDefinition::Object(o)) = def {
-                if o.name == entity_type {
-                    Some(o)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .unwrap_or_else(|| {
-            logging::critical!("Something went wrong! Could not find the entity defined in the GraphQL schema.")
-        })
-        .fields
-        .iter()
-        .clone()
-        .filter(|&f| matches!(f.field_type, schema::Type::NonNullType(..)) && !f.is_derived());
+        Ok(true)
+    }
 
-        for f in required_fields {
-            if !data.contains_key(&f.name) {
-                return Err(anyhow!(
-                    "Missing value for non-nullable field '{}' for an entity of type '{}'.",
-                    f.name,
-                    entity_type,
-                )
-                .into());
-            } else if let Value::Null = data.get(&f.name).unwrap() {
-                return Err(anyhow!(
-                    "The required field '{}' for an entity of type '{}' is null.",
-                    f.name,
-                    entity_type,
-                )
-                .into());
-            }
+    /// function _assert.entityWithFields(entityType: string, fields: Entity): bool
+    /// Checks that at least one entity of the type has all of the passed field values.
+    pub fn assert_entity_with_fields(
+        &mut self,
+        _gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        fields_ptr: AscPtr<AscEntity>,
+    ) -> Result<bool, HostExportError> {
+        update_derived_relations_in_store(self);
+        let message = self.assertion_message.take();
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let fields: HashMap<String, Value> =
+            try_asc_get(&self.wasm_ctx, fields_ptr, &GasCounter::new())?;
+
+        let empty = HashMap::new();
+        let entities = self.store.get(&entity_type).unwrap_or(&empty);
+        if entities
+            .values()
+            .any(|entity| assertions::matches_fields(entity, &fields))
+        {
+            return Ok(true);
         }
 
-        if self.derived.contains_key(&entity_type) {
-            let linking_fields = self
-                .derived
-                .get(&entity_type)
-                .unwrap_or_else(|| {
-                    logging::critical!("Couldn't find value for key {} in derived map", entity_type)
-                })
-                .clone();
-            for linking_field in linking_fields {
-                if data.contains_key(&linking_field.1) && self.store.contains_key(&linking_field.2)
-                {
-                    let original_entity_type = linking_field.2.clone();
-                    let derived_field_value = data
-                        .get(&linking_field.1)
-                        .unwrap_or_else(|| {
-                            logging::critical!(
-                                "Couldn't find value for {} in submitted data",
-                                linking_field.1
-                            )
-                        })
-                        .clone();
-                    if matches!(derived_field_value, Value::List(_)) {
-                        for derived_field_value in derived_field_value.as_list().unwrap().clone() {
-                            insert_derived_field_in_store(
-                                self,
-                                derived_field_value,
-                                original_entity_type.clone(),
-                                linking_field.clone(),
-                                id.clone(),
-                            );
-                        }
-                    } else {
-                        insert_derived_field_in_store(
-                            self,
-                            derived_field_value,
-                            original_entity_type.clone(),
-                            linking_field.clone(),
-                            id.clone(),
-                        );
-                    }
+        let mut expected: Vec<String> = fields
+            .iter()
+            .map(|(field, value)| format!("{}: {}", field, format_typed_value(value)))
+            .collect();
+        expected.sort();
+        let near_matches: Vec<String> = assertions::near_matches(entities, &fields)
+            .into_iter()
+            .map(|(id, diff)| format!("'{}':\n  {}", id, diff.join("\n  ")))
+            .collect();
+
+        assertions::log_failure(
+            &message,
+            format!(
+                "(assert.entityWithFields) No entity with type '{}' has the fields {{{}}}.{}",
+                &entity_type,
+                expected.join(", "),
+                if near_matches.is_empty() {
+                    String::new()
+                } else {
+                    format!(" Closest matches:\n{}", near_matches.join("\n"))
                 }
-            }
+            ),
+        );
+        Ok(false)
+    }
+
+    /// function _assert.notInStore(entityType: string, id: string): bool
+    pub fn assert_not_in_store(
+        &mut self,
+        _gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        update_derived_relations_in_store(self);
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
+
+        if self.store.contains_key(&entity_type)
+            && self.store.get(&entity_type).unwrap().contains_key(&id)
+        {
+            assertions::log_failure(
+                &message,
+                format!(
+                "(assert.notInStore) Value for entity type: '{}' and id: '{}' was found in store.",
+                entity_type,
+                id
+            ),
+            );
+            return Ok(false);
         }
 
-        let mut entity_type_store = if self.store.contains_key(&entity_type) {
-            self.store.get(&entity_type).unwrap().clone()
-        } else {
-            HashMap::new()
-        };
+        Ok(true)
+    }
 
-        // Collect all child entities for the passed entity_type
-        let child_entities: HashMap<String, Vec<(String, String, String)>> = self
-            .derived
-            .iter()
-            .filter_map(|(linked_entity, linking_fields)| {
-                let mapping = linking_fields
-                    .iter()
-                    .filter(|linking_field| linking_field.2 == entity_type);
+    /// function store.get(entityType: string, id: string): Entity
+    pub fn mock_store_get(
+        &mut self,
+        _gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<AscEntity>, HostExportError> {
+        update_derived_relations_in_store(self);
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
+        self.store_operations.push(StoreOperation {
+            kind: OperationKind::Get,
+            entity_type: entity_type.clone(),
+            id: id.clone(),
+            changed_fields: vec![],
+        });
 
-                if mapping.count() > 0 {
-                    Some((linked_entity.clone(), linking_fields.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+        if self.store.contains_key(&entity_type)
+            && self.store.get(&entity_type).unwrap().contains_key(&id)
+        {
+            let entities = self.store.get(&entity_type).unwrap();
+            let entity = entities.get(&id).unwrap().clone();
+            let entity = Entity::from(entity);
 
-        // Iterate over all child entities
-        // Fetch all saved records
-        // Collect the ids of the records which derivedFrom field points to the passed entity id
-        // Update the parent's data with the list of child records
-        if !child_entities.is_empty() {
-            for (linked_entity, linking_fields) in child_entities.iter() {
-                for linking_field in linking_fields.iter() {
-                    if let Some(entities) = self.store.get(linked_entity) {
-                        let children: Vec<Value> = entities
-                            .iter()
-                            .filter_map(|(child_id, fields)| {
-                                if let Some(Value::String(parent_id)) = fields.get(&linking_field.1)
-                                {
-                                    if parent_id == &id {
-                                        Some(Value::String(child_id.clone()))
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-
-                        data.insert(linking_field.0.clone(), Value::List(children));
-                    }
-                }
-            }
+            let res = asc_new(&mut self.wasm_ctx, &entity.sorted(), &GasCounter::new())?;
+            return Ok(res);
         }
 
-        entity_type_store.insert(id, data);
-        self.store.insert(entity_type, entity_type_store);
-        self.store_updated = false;
+        Ok(AscPtr::null())
+    }
+
+    /// function store.set(entityType: string, id: string, data: map): void
+    pub fn mock_store_set(
+        &mut self,
+        _gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        id_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<AscEntity>,
+    ) -> Result<(), HostExportError> {
+        let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
+        let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let data: HashMap<String, Value> =
+            try_asc_get(&self.wasm_ctx, data_ptr, &GasCounter::new())?;
+
+        let key = store_key(&entity_type, &id);
+        let changed_fields = store_operations::changed_fields(
+            self.store
+                .get(&entity_type)
+                .and_then(|entities| entities.get(&key)),
+            &data,
+        );
+
+        self.store_set(entity_type.clone(), id, data)?;
+        self.store_operations.push(StoreOperation {
+            kind: OperationKind::Set,
+            entity_type,
+            id: key,
+            changed_fields,
+        });
         Ok(())
     }
 
@@ -530,6 +1709,13 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
     ) -> Result<(), HostExportError> {
         let entity_type: String = asc_get(&self.wasm_ctx, entity_type_ptr, &GasCounter::new())?;
         let id: String = asc_get(&self.wasm_ctx, id_ptr, &GasCounter::new())?;
+        let id = store_key(&entity_type, &id);
+        self.store_operations.push(StoreOperation {
+            kind: OperationKind::Remove,
+            entity_type: entity_type.clone(),
+            id: id.clone(),
+            changed_fields: vec![],
+        });
 
         if self.store.contains_key(&entity_type)
             && self.store.get(&entity_type).unwrap().contains_key(&id)
@@ -580,30 +1766,95 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
             &fn_args,
         );
 
-        let return_val;
-        if self.fn_ret_map.contains_key(&fn_id) {
-            if *self.fn_ret_map.get(&fn_id).unwrap() == REVERTS_IDENTIFIER.clone() {
-                return Ok(AscPtr::null());
+        // Sequences take precedence until they are exhausted, then the mocks with exact arguments
+        // and last the mocks with argument matchers
+        let next_value = match self.fn_sequences.get_mut(&fn_id) {
+            Some(sequence) => sequence.next_value(),
+            None => NextValue::Fallback,
+        };
+        let exhausted = next_value == NextValue::Fail;
+        let matcher_mock = find_matcher_mock(
+            &self.matcher_mocks,
+            &call.contract_address,
+            &fn_name,
+            &fn_signature,
+            &fn_args,
+        );
+        let result = match (next_value, self.fn_ret_map.get(&fn_id)) {
+            (NextValue::Value(result), _) => Some(result),
+            (NextValue::Fail, _) => None,
+            (NextValue::Fallback, Some(result)) => {
+                if let Some(mock) = self.exact_mocks.get_mut(&fn_id) {
+                    mock.hits += 1;
+                }
+                Some(result.clone())
             }
+            (NextValue::Fallback, None) => matcher_mock.map(|index| {
+                let mock = &mut self.matcher_mocks[index];
+                mock.hits += 1;
+                mock.result.clone()
+            }),
+        };
 
-            return_val = asc_new(
-                &mut self.wasm_ctx,
-                self.fn_ret_map
-                    .get(&fn_id)
-                    .unwrap_or_else(|| logging::critical!("Could not get value from function map."))
-                    .as_slice(),
-                &GasCounter::new(),
-            )?;
+        let contract_call = ContractCall {
+            contract_address: call.contract_address,
+            fn_name: fn_name.clone(),
+            fn_signature: fn_signature.clone(),
+            args: fn_args.clone(),
+        };
+        // Calls which are not mocked are answered from the cassette, if there is one
+        let result = match (result, self.cassette.as_mut()) {
+            (None, Some(cassette)) if !exhausted => cassette
+                .call(&contract_call)
+                .map_err(|err| anyhow!("{:#}", err))?,
+            (result, _) => result,
+        };
 
-            Ok(return_val)
-        } else {
-            Err(anyhow!(
+        let revert = match &result {
+            Some(MockResult::Revert(reason)) => Some(reason.clone()),
+            _ => None,
+        };
+        self.contract_calls.push(RecordedCall {
+            call: contract_call,
+            mocked: result.is_some(),
+            revert,
+        });
+
+        match result {
+            Some(MockResult::Return(return_value)) => {
+                let return_val = asc_new(
+                    &mut self.wasm_ctx,
+                    return_value.as_slice(),
+                    &GasCounter::new(),
+                )?;
+                Ok(return_val)
+            }
+            // A null result makes the `try_` calls return a reverted result
+            // and all other calls abort, the same as with a real reverting call
+            Some(MockResult::Revert(reason)) => {
+                logging::info!(
+                    "Mocked call to {} on {:?} with {:?} reverted {}",
+                    &fn_signature,
+                    &call.contract_address,
+                    &fn_args,
+                    reason
+                );
+                Ok(AscPtr::null())
+            }
+            None if exhausted => Err(anyhow!(
+                "The return values mocked with `mockFunctionOnce` for {} on {:?} with {:?} have all been consumed.",
+                &fn_signature,
+                &call.contract_address,
+                &fn_args
+            )
+            .into()),
+            None => Err(anyhow!(
                 "Could not find a mocked function with the following parameters, address: {}, name: {}, signature {}, params: {:?}.",
                 &contract_address,
                 &fn_name,
                 &fn_signature,
                 &fn_args
-            ).into())
+            ).into()),
         }
     }
 
@@ -622,11 +1873,157 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         return_value_ptr: u32,
         reverts_ptr: AscPtr<bool>,
     ) -> Result<(), HostExportError> {
-        let contract_address: Address = asc_get(
-            &self.wasm_ctx,
-            contract_address_ptr.into(),
-            &GasCounter::new(),
+        let call = self.read_mocked_call(
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+        )?;
+        let result = self.read_mocked_result(return_value_ptr, reverts_ptr)?;
+        let fn_id = MatchstickInstanceContext::<C>::call_fn_id(&call);
+
+        self.exact_mocks
+            .insert(fn_id.clone(), ExactMock { call, hits: 0 });
+        self.fn_ret_map.insert(fn_id, result);
+
+        Ok(())
+    }
+
+    /// function mockFunctionOnce(
+    ///     contractAddress: Address, fnName: string, fnSignature: string,
+    ///     fnArgs: ethereum.Value[], returnValue: ethereum.Value[], reverts: bool,
+    /// ): void
+    /// Appends a return value to the sequence of the function, every call consumes the next value.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mock_function_once(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: u32,
+        fn_name_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+        return_value_ptr: u32,
+        reverts_ptr: AscPtr<bool>,
+    ) -> Result<(), HostExportError> {
+        let call = self.read_mocked_call(
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+        )?;
+        let result = self.read_mocked_result(return_value_ptr, reverts_ptr)?;
+        let fn_id = MatchstickInstanceContext::<C>::call_fn_id(&call);
+
+        self.fn_sequences
+            .entry(fn_id)
+            .or_insert_with(|| MockSequence::new(call))
+            .push(result);
+        Ok(())
+    }
+
+    /// function mockFunctionRevert(
+    ///     contractAddress: Address, fnName: string, fnSignature: string,
+    ///     fnArgs: ethereum.Value[], kind: string, data: string, once: bool,
+    /// ): void
+    /// Mocks the function to revert with a reason (`kind` is `reason` and `data` the reason string),
+    /// a custom error (`customError` with the ABI-encoded error as hex) or `outOfGas`.
+    /// With `once` the revert is appended to the sequence of the function like with `mockFunctionOnce`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mock_function_revert(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: u32,
+        fn_name_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+        kind_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<AscString>,
+        once_ptr: AscPtr<bool>,
+    ) -> Result<(), HostExportError> {
+        let call = self.read_mocked_call(
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
         )?;
+        let kind: String = asc_get(&self.wasm_ctx, kind_ptr, &GasCounter::new())?;
+        let data: String = asc_get(&self.wasm_ctx, data_ptr, &GasCounter::new())?;
+        let once = bool::from(EnumPayload(once_ptr.to_payload()));
+        let reason = RevertReason::parse(&kind, &data)
+            .map_err(|err| anyhow!("mockFunctionRevert: {}", err))?;
+        let fn_id = MatchstickInstanceContext::<C>::call_fn_id(&call);
+
+        if once {
+            self.fn_sequences
+                .entry(fn_id)
+                .or_insert_with(|| MockSequence::new(call))
+                .push(MockResult::Revert(reason));
+        } else {
+            self.exact_mocks
+                .insert(fn_id.clone(), ExactMock { call, hits: 0 });
+            self.fn_ret_map.insert(fn_id, MockResult::Revert(reason));
+        }
+        Ok(())
+    }
+
+    /// function mockFunctionWhenExhausted(
+    ///     contractAddress: Address, fnName: string, fnSignature: string,
+    ///     fnArgs: ethereum.Value[], behavior: string,
+    /// ): void
+    /// Sets what the function returns once its sequence is exhausted: `fallback` (the default)
+    /// to the other mocks of the function, `repeatLast`, `cycle`, `revert` or `fail`.
+    pub fn mock_function_when_exhausted(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: u32,
+        fn_name_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+        behavior_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let call = self.read_mocked_call(
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+        )?;
+        let behavior: String = asc_get(&self.wasm_ctx, behavior_ptr, &GasCounter::new())?;
+        let when_exhausted: WhenExhausted = behavior
+            .parse()
+            .map_err(|err| anyhow!("mockFunctionWhenExhausted: {}", err))?;
+
+        let fn_id = MatchstickInstanceContext::<C>::call_fn_id(&call);
+        let sequence = self.fn_sequences.get_mut(&fn_id).ok_or_else(|| {
+            anyhow!(
+                "mockFunctionWhenExhausted: no return values have been mocked with `mockFunctionOnce` for {}",
+                call
+            )
+        })?;
+        sequence.when_exhausted = when_exhausted;
+        Ok(())
+    }
+
+    /// function mockFunctionWithMatchers(
+    ///     contractAddress: string, fnName: string, fnSignature: string,
+    ///     fnArgs: ethereum.Value[], argMatchers: string[], returnValue: ethereum.Value[], reverts: bool,
+    /// ): void
+    /// An empty `contractAddress` or `*` matches calls to any contract. Every argument has a matcher:
+    /// `eq` (or empty) for the passed value, `any`, `oneOf` for an array of allowed values,
+    /// or `type:<abi type>` for any value of the type, e.g. `type:address`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn mock_function_with_matchers(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: AscPtr<AscString>,
+        fn_name_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+        arg_matchers_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        return_value_ptr: u32,
+        reverts_ptr: AscPtr<bool>,
+    ) -> Result<(), HostExportError> {
+        let contract_address: String =
+            asc_get(&self.wasm_ctx, contract_address_ptr, &GasCounter::new())?;
         let fn_name: String = asc_get(&self.wasm_ctx, fn_name_ptr, &GasCounter::new())?;
         let fn_signature: String = asc_get(&self.wasm_ctx, fn_signature_ptr, &GasCounter::new())?;
         let fn_args: Vec<Token> = asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
@@ -634,81 +2031,232 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
             fn_args_ptr.into(),
             &GasCounter::new(),
         )?;
-        let return_value: Vec<Token> = asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
-            &self.wasm_ctx,
-            return_value_ptr.into(),
-            &GasCounter::new(),
-        )?;
-        let reverts = bool::from(EnumPayload(reverts_ptr.to_payload()));
-
-        // Extracts the arguments part from the function signature
-        // e.g "fnName(int32, string, address)" -> "int32, string, address"
-        // and then calls `collect_types` to split the result into a Vec
-        let tmp_str = fn_signature.replace(&(fn_name.clone() + "("), "");
-        let components: Vec<&str> = tmp_str.split("):").collect();
-        let tmp_args_str = components[0];
-        let arg_types: Vec<String> = collect_types(tmp_args_str);
-
-        let fn_signature_split: Vec<&str> = fn_signature.split('(').collect();
-        if fn_name != fn_signature_split[0] {
+        let fn_signature = self
+            .manifest
+            .abis
+            .resolve_signature(&fn_name, &fn_signature, &fn_args)
+            .map_err(|err| anyhow!("createMockedFunction: {}", err))?;
+        let arg_matchers: Vec<String> =
+            asc_get(&self.wasm_ctx, arg_matchers_ptr, &GasCounter::new())?;
+        let result = self.read_mocked_result(return_value_ptr, reverts_ptr)?;
+
+        let contract_address = parse_contract_address(&contract_address)
+            .map_err(|err| anyhow!("createMockedFunction: {}", err))?;
+
+        let arg_types =
+            MatchstickInstanceContext::<C>::signature_arg_types(&fn_name, &fn_signature)?;
+        if arg_types.len() != fn_args.len() || arg_types.len() != arg_matchers.len() {
             return Err(anyhow!(
-                "createMockedFunction: function name `{}` should match the name in the function signature `{}`",
-                fn_name,
-                fn_signature
-            ).into());
-        }
-
-        // Checks if the count of the passed arguments matches the count of expected arguments
-        if arg_types.len() != fn_args.len() {
-            return Err(anyhow!(
-                "{} expected {} arguments, but received {}",
+                "{} expected {} arguments and matchers, but received {} arguments and {} matchers",
                 fn_name,
                 arg_types.len(),
-                fn_args.len()
+                fn_args.len(),
+                arg_matchers.len()
             )
             .into());
         }
 
-        // Validates that every passed argument matches the type of the expected argument
-        // from the function signature. Panics if there is a mismatch and informs the user
-        // of the position and the expected and recieved type
-        for (index, (arg_type, fn_arg)) in arg_types.iter().zip(fn_args.iter()).enumerate() {
-            let param_type = get_kind(arg_type.to_owned());
+        let matchers = arg_types
+            .iter()
+            .zip(fn_args.into_iter().zip(arg_matchers.iter()))
+            .enumerate()
+            .map(|(index, (arg_type, (fn_arg, matcher)))| {
+                ArgMatcher::parse(matcher, fn_arg, &get_kind(arg_type.to_owned())).map_err(|err| {
+                    anyhow!(
+                        "createMockedFunction `{}` invalid matcher at position {}: {}",
+                        fn_name,
+                        index + 1,
+                        err
+                    )
+                })
+            })
+            .collect::<Result<Vec<ArgMatcher>, anyhow::Error>>()?;
+
+        self.matcher_mocks.push(MatcherMock {
+            contract_address,
+            fn_name,
+            fn_signature,
+            matchers,
+            result,
+            hits: 0,
+        });
 
-            if !fn_arg.type_check(&param_type) {
-                return Err(anyhow!(
-                    "createMockedFunction `{}` parameters mismatch at position {}:\nExpected: {:?}\nRecieved: {:?}\n",
-                    fn_name,
-                    index + 1,
-                    param_type,
-                    fn_arg
-                ).into());
-            }
-        }
+        Ok(())
+    }
 
-        let fn_id = MatchstickInstanceContext::<C>::fn_id(
-            &contract_address.to_string(),
-            &fn_name,
-            &fn_signature,
-            &fn_args,
-        );
+    /// function logContractCalls(): void
+    pub fn log_contract_calls(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        logging::debug!("{}", self.format_contract_calls());
+        Ok(())
+    }
 
-        if reverts {
-            self.fn_ret_map.insert(fn_id, REVERTS_IDENTIFIER.clone());
+    /// function logUnusedMocks(): void
+    pub fn log_unused_mocks(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        let unused = self.unused_mocks();
+        if unused.is_empty() {
+            logging::debug!("All mocked functions have been called.");
         } else {
-            self.fn_ret_map.insert(fn_id, return_value);
+            logging::debug!(
+                "Mocked functions which have never been called:\n{}",
+                unused.join("\n")
+            );
         }
-
         Ok(())
     }
 
+    /// function _assert.contractCallCount(
+    ///     contractAddress: string, fnSignature: string, fnArgs: ethereum.Value[], expectedCount: u32,
+    /// ): bool
+    /// An empty `contractAddress` or `*` counts the calls to all contracts.
+    pub fn assert_contract_call_count(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        fn_args_ptr: u32,
+        expected_count: u32,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let contract_address: String =
+            asc_get(&self.wasm_ctx, contract_address_ptr, &GasCounter::new())?;
+        let fn_signature: String = asc_get(&self.wasm_ctx, fn_signature_ptr, &GasCounter::new())?;
+        let fn_args: Vec<Token> = asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
+            &self.wasm_ctx,
+            fn_args_ptr.into(),
+            &GasCounter::new(),
+        )?;
+        let address = parse_contract_address(&contract_address)
+            .map_err(|err| anyhow!("(assert.contractCallCount) {}", err))?;
+
+        let count = self
+            .contract_calls
+            .iter()
+            .filter(|recorded| {
+                recorded
+                    .call
+                    .matches(address.as_ref(), &fn_signature, Some(fn_args.as_slice()))
+            })
+            .count() as u32;
+
+        if count != expected_count {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.contractCallCount) Expected {} to be called {} times on {} with {:?}, but it was called {} times. Recorded calls:\n{}",
+                    fn_signature,
+                    expected_count,
+                    address.map_or("any contract".to_owned(), |address| format!("{:?}", address)),
+                    fn_args,
+                    count,
+                    self.format_contract_calls()
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.contractNotCalled(contractAddress: string, fnSignature: string): bool
+    /// An empty `contractAddress` or `*` checks the calls to all contracts.
+    pub fn assert_contract_not_called(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let contract_address: String =
+            asc_get(&self.wasm_ctx, contract_address_ptr, &GasCounter::new())?;
+        let fn_signature: String = asc_get(&self.wasm_ctx, fn_signature_ptr, &GasCounter::new())?;
+        let address = parse_contract_address(&contract_address)
+            .map_err(|err| anyhow!("(assert.contractNotCalled) {}", err))?;
+
+        let calls: Vec<String> = self
+            .contract_calls
+            .iter()
+            .filter(|recorded| recorded.call.matches(address.as_ref(), &fn_signature, None))
+            .map(|recorded| recorded.to_string())
+            .collect();
+
+        if !calls.is_empty() {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.contractNotCalled) Expected {} not to be called on {}, but it was called {} times:\n{}",
+                    fn_signature,
+                    address.map_or("any contract".to_owned(), |address| format!("{:?}", address)),
+                    calls.len(),
+                    calls.join("\n")
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.contractCallReverted(contractAddress: string, fnSignature: string, reason: string): bool
+    /// Checks that the last call of the function reverted, with the reason if it is not empty.
+    /// An empty `contractAddress` or `*` checks the last call on any contract.
+    pub fn assert_contract_call_reverted(
+        &mut self,
+        _gas: &GasCounter,
+        contract_address_ptr: AscPtr<AscString>,
+        fn_signature_ptr: AscPtr<AscString>,
+        reason_ptr: AscPtr<AscString>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let contract_address: String =
+            asc_get(&self.wasm_ctx, contract_address_ptr, &GasCounter::new())?;
+        let fn_signature: String = asc_get(&self.wasm_ctx, fn_signature_ptr, &GasCounter::new())?;
+        let reason: String = asc_get(&self.wasm_ctx, reason_ptr, &GasCounter::new())?;
+        let address = parse_contract_address(&contract_address)
+            .map_err(|err| anyhow!("(assert.contractCallReverted) {}", err))?;
+        let on = address.map_or("any contract".to_owned(), |address| {
+            format!("{:?}", address)
+        });
+
+        let last_call = self
+            .contract_calls
+            .iter()
+            .rev()
+            .find(|recorded| recorded.call.matches(address.as_ref(), &fn_signature, None));
+
+        let failure = match last_call.map(|recorded| &recorded.revert) {
+            None => format!("{} was never called on {}", fn_signature, on),
+            Some(None) => format!("the last call of {} on {} did not revert", fn_signature, on),
+            Some(Some(revert)) if !reason.is_empty() && !revert.matches(&reason) => format!(
+                "the last call of {} on {} reverted {}, not with `{}`",
+                fn_signature, on, revert, reason
+            ),
+            Some(Some(_)) => return Ok(true),
+        };
+
+        assertions::log_failure(
+            &message,
+            format!(
+                "(assert.contractCallReverted) Expected {} to revert, but {}. Recorded calls:\n{}",
+                fn_signature,
+                failure,
+                self.format_contract_calls()
+            ),
+        );
+        Ok(false)
+    }
+
     /// function dataSource.create(name: string, params: Array<string>): void
     pub fn mock_data_source_create(
         &mut self,
         _gas: &GasCounter,
-        _name_ptr: AscPtr<AscString>,
-        _params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        name_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
     ) -> Result<(), HostExportError> {
+        let name: String = asc_get(&self.wasm_ctx, name_ptr, &GasCounter::new())?;
+        let params: Vec<String> = asc_get(&self.wasm_ctx, params_ptr, &GasCounter::new())?;
+
+        self.create_data_source(name, params, None)
+            .map_err(|err| anyhow!("dataSource.create: {}", err))?;
         Ok(())
     }
 
@@ -719,58 +2267,183 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
     pub fn mock_data_source_create_with_context(
         &mut self,
         _gas: &GasCounter,
-        _name_ptr: AscPtr<AscString>,
-        _params_ptr: AscPtr<Array<AscPtr<AscString>>>,
-        _context_ptr: AscPtr<AscEntity>,
+        name_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        context_ptr: AscPtr<AscEntity>,
     ) -> Result<(), HostExportError> {
+        let name: String = asc_get(&self.wasm_ctx, name_ptr, &GasCounter::new())?;
+        let params: Vec<String> = asc_get(&self.wasm_ctx, params_ptr, &GasCounter::new())?;
+        let context: HashMap<String, Value> =
+            try_asc_get(&self.wasm_ctx, context_ptr, &GasCounter::new())?;
+
+        self.create_data_source(name, params, Some(context.into_iter().collect()))
+            .map_err(|err| anyhow!("dataSource.createWithContext: {}", err))?;
+        Ok(())
+    }
+
+    /// function logDataSources(): void
+    pub fn log_data_sources(&mut self, _gas: &GasCounter) -> Result<(), HostExportError> {
+        logging::debug!("{}", self.format_data_sources());
         Ok(())
     }
 
+    /// function _assert.dataSourceCreated(template: string, params: Array<string>): bool
+    pub fn assert_data_source_created(
+        &mut self,
+        _gas: &GasCounter,
+        template_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let template: String = asc_get(&self.wasm_ctx, template_ptr, &GasCounter::new())?;
+        let params: Vec<String> = asc_get(&self.wasm_ctx, params_ptr, &GasCounter::new())?;
+
+        if !self
+            .created_data_sources
+            .iter()
+            .any(|data_source| data_source.matches(&template, &params, None))
+        {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.dataSourceCreated) Expected a {} data source to be created with params {:?}. Created data sources:\n{}",
+                    template,
+                    params,
+                    self.format_data_sources()
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.dataSourceCreatedWithContext(
+    ///     template: string, params: Array<string>, context: DataSourceContext,
+    /// ): bool
+    pub fn assert_data_source_created_with_context(
+        &mut self,
+        _gas: &GasCounter,
+        template_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        context_ptr: AscPtr<AscEntity>,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let template: String = asc_get(&self.wasm_ctx, template_ptr, &GasCounter::new())?;
+        let params: Vec<String> = asc_get(&self.wasm_ctx, params_ptr, &GasCounter::new())?;
+        let context: BTreeMap<String, Value> = try_asc_get::<HashMap<String, Value>, _, _>(
+            &self.wasm_ctx,
+            context_ptr,
+            &GasCounter::new(),
+        )?
+        .into_iter()
+        .collect();
+
+        if !self
+            .created_data_sources
+            .iter()
+            .any(|data_source| data_source.matches(&template, &params, Some(&context)))
+        {
+            let expected = CreatedDataSource {
+                template,
+                params,
+                context: Some(context),
+            };
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.dataSourceCreatedWithContext) Expected a data source {} to be created. Created data sources:\n{}",
+                    expected,
+                    self.format_data_sources()
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// function _assert.dataSourceCount(template: string, expectedCount: u32): bool
+    /// An empty `template` counts the data sources created from all templates.
+    pub fn assert_data_source_count(
+        &mut self,
+        _gas: &GasCounter,
+        template_ptr: AscPtr<AscString>,
+        expected_count: u32,
+    ) -> Result<bool, HostExportError> {
+        let message = self.assertion_message.take();
+        let template: String = asc_get(&self.wasm_ctx, template_ptr, &GasCounter::new())?;
+
+        let count = self
+            .created_data_sources
+            .iter()
+            .filter(|data_source| template.is_empty() || data_source.template == template)
+            .count() as u32;
+
+        if count != expected_count {
+            assertions::log_failure(
+                &message,
+                format!(
+                    "(assert.dataSourceCount) Expected {} data sources to be created from {}, but {} were created. Created data sources:\n{}",
+                    expected_count,
+                    if template.is_empty() { "all templates" } else { template.as_str() },
+                    count,
+                    self.format_data_sources()
+                ),
+            );
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
     /// function dataSource.address(): Address
+    /// Defaults to the address of the tested data source in the manifest, or the zero address.
     pub fn mock_data_source_address(
         &mut self,
         _gas: &GasCounter,
     ) -> Result<AscPtr<Uint8Array>, HostExportError> {
         let default_address_val = "0x0000000000000000000000000000000000000000";
-        let result = match &self.data_source_return_value.0 {
-            Some(value) => asc_new(
-                &mut self.wasm_ctx,
-                &Address::from_str(value).expect("Couldn't create Address."),
-                &GasCounter::new(),
-            )
-            .expect("Couldn't create pointer."),
-            None => asc_new(
-                &mut self.wasm_ctx,
-                &Address::from_str(default_address_val).expect("Couldn't create Address."),
-                &GasCounter::new(),
-            )
-            .expect("Couldn't create pointer."),
+        let address = match &self.data_source_return_value.0 {
+            Some(value) => value.clone(),
+            None => self
+                .tested_data_source
+                .as_ref()
+                .and_then(|data_source| data_source.address.clone())
+                .unwrap_or_else(|| default_address_val.to_owned()),
         };
+        let address = Address::from_str(&address)
+            .map_err(|err| anyhow!("dataSource.address: invalid address `{}`: {}", address, err))?;
+
+        let result = asc_new(&mut self.wasm_ctx, &address, &GasCounter::new())
+            .expect("Couldn't create pointer.");
 
         Ok(result)
     }
 
     /// function dataSource.network(): String
+    /// Defaults to the network of the tested data source in the manifest, or mainnet.
     pub fn mock_data_source_network(
         &mut self,
         _gas: &GasCounter,
     ) -> Result<AscPtr<AscString>, HostExportError> {
         let default_network_val = "mainnet";
-        let result = match &self.data_source_return_value.1 {
-            Some(value) => AscPtr::alloc_obj(
-                asc_string_from_str(&value.clone()),
-                &mut self.wasm_ctx,
-                &GasCounter::new(),
-            )
-            .expect("Couldn't create pointer."),
-            None => AscPtr::alloc_obj(
-                asc_string_from_str(default_network_val),
-                &mut self.wasm_ctx,
-                &GasCounter::new(),
-            )
-            .expect("Couldn't create pointer."),
+        let network = match &self.data_source_return_value.1 {
+            Some(value) => value.clone(),
+            None => self
+                .tested_data_source
+                .as_ref()
+                .map(|data_source| data_source.network.clone())
+                .unwrap_or_else(|| default_network_val.to_owned()),
         };
 
+        let result = AscPtr::alloc_obj(
+            asc_string_from_str(&network),
+            &mut self.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+
         Ok(result)
     }
 
@@ -815,6 +2488,79 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
         Ok(())
     }
 
+    /// function dataSourceMock.useCreated(template: string, address: string): void
+    /// Makes `dataSource.address()`, `dataSource.network()` and `dataSource.context()` return
+    /// the values of the last data source created from the template with the address,
+    /// so handlers can be called as that instance. An empty `template` matches all templates.
+    pub fn use_created_data_source(
+        &mut self,
+        _gas: &GasCounter,
+        template_ptr: AscPtr<AscString>,
+        address_ptr: AscPtr<AscString>,
+    ) -> Result<(), HostExportError> {
+        let template: String = asc_get(&self.wasm_ctx, template_ptr, &GasCounter::new())?;
+        let address: String = asc_get(&self.wasm_ctx, address_ptr, &GasCounter::new())?;
+        let address = Address::from_str(&address).map_err(|err| {
+            anyhow!(
+                "dataSourceMock.useCreated: invalid address `{}`: {}",
+                address,
+                err
+            )
+        })?;
+
+        let data_source = self
+            .created_data_sources
+            .iter()
+            .rev()
+            .find(|data_source| {
+                (template.is_empty() || data_source.template == template)
+                    && data_source.address() == Some(address)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "dataSourceMock.useCreated: no data source was created from {} with address {:?}. Created data sources:\n{}",
+                    if template.is_empty() { "any template" } else { template.as_str() },
+                    address,
+                    self.format_data_sources()
+                )
+            })?;
+        let network = self
+            .manifest
+            .templates
+            .as_ref()
+            .and_then(|templates| {
+                templates
+                    .iter()
+                    .find(|(name, _)| *name == data_source.template)
+                    .map(|(_, network)| network.clone())
+            })
+            .unwrap_or_else(|| "mainnet".to_owned());
+
+        self.data_source_return_value = (
+            Some(format!("{:?}", address)),
+            Some(network),
+            Some(
+                data_source
+                    .context
+                    .clone()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+            ),
+        );
+        Ok(())
+    }
+
+    /// function dataSourceMock.resetValues(): void
+    /// Brings back the default `dataSource.address()`, `dataSource.network()` and `dataSource.context()`.
+    pub fn reset_data_source_return_values(
+        &mut self,
+        _gas: &GasCounter,
+    ) -> Result<(), HostExportError> {
+        self.data_source_return_value = (None, None, None);
+        Ok(())
+    }
+
     /// function countEntities(entityType: string): i32
     pub fn count_entities(
         &mut self,
@@ -921,9 +2667,22 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
 
             instance.instance_ctx_mut().store = self.store.clone();
             instance.instance_ctx_mut().fn_ret_map = self.fn_ret_map.clone();
+            instance.instance_ctx_mut().matcher_mocks = self.matcher_mocks.clone();
+            instance.instance_ctx_mut().exact_mocks = self.exact_mocks.clone();
+            instance.instance_ctx_mut().fn_sequences = self.fn_sequences.clone();
+            instance.instance_ctx_mut().contract_calls = self.contract_calls.clone();
+            instance.instance_ctx_mut().cassette = self.cassette.clone();
+            instance.instance_ctx_mut().created_data_sources = self.created_data_sources.clone();
             instance.instance_ctx_mut().derived = self.derived.clone();
             instance.instance_ctx_mut().data_source_return_value =
                 self.data_source_return_value.clone();
+            instance.instance_ctx_mut().store_operations = self.store_operations.clone();
+            instance.instance_ctx_mut().test_file = self.test_file.clone();
+            instance.instance_ctx_mut().tested_data_source = self.tested_data_source.clone();
+            instance.instance_ctx_mut().assertion_message = self.assertion_message.clone();
+            instance.instance_ctx_mut().pending_block = self.pending_block.clone();
+            instance.instance_ctx_mut().committed_blocks = self.committed_blocks.clone();
+            instance.instance_ctx_mut().store_before_blocks = self.store_before_blocks.clone();
 
             instance
                 .instance
@@ -935,9 +2694,20 @@ impl<C: Blockchain> MatchstickInstanceContext<C> {
 
             self.store = instance.instance_ctx().store.clone();
             self.fn_ret_map = instance.instance_ctx().fn_ret_map.clone();
+            self.matcher_mocks = instance.instance_ctx().matcher_mocks.clone();
+            self.exact_mocks = instance.instance_ctx().exact_mocks.clone();
+            self.fn_sequences = instance.instance_ctx().fn_sequences.clone();
+            self.contract_calls = instance.instance_ctx().contract_calls.clone();
+            self.cassette = instance.instance_ctx().cassette.clone();
+            self.created_data_sources = instance.instance_ctx().created_data_sources.clone();
             self.derived = instance.instance_ctx().derived.clone();
             self.data_source_return_value =
                 instance.instance_ctx().data_source_return_value.clone();
+            self.store_operations = instance.instance_ctx().store_operations.clone();
+            self.assertion_message = instance.instance_ctx().assertion_message.clone();
+            self.pending_block = instance.instance_ctx().pending_block.clone();
+            self.committed_blocks = instance.instance_ctx().committed_blocks.clone();
+            self.store_before_blocks = instance.instance_ctx().store_before_blocks.clone();
         }
 
         Ok(())
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use graph::data::graphql::ext::DirectiveFinder;
+use graph::data::store::Value;
+use graph_graphql::graphql_parser::schema;
+
+use crate::context::derived_fields::linked_ids;
+use crate::context::{get_entity_definition, SCHEMA};
+
+type Store = HashMap<String, HashMap<String, HashMap<String, Value>>>;
+
+/// A field of a stored entity which points to an entity that is not in the store.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DanglingReference {
+    pub(crate) entity_type: String,
+    pub(crate) id: String,
+    pub(crate) field: String,
+    pub(crate) target_type: String,
+    pub(crate) target_id: String,
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' field '{}' references {} '{}', which is not in the store",
+            self.entity_type, self.id, self.field, self.target_type, self.target_id
+        )
+    }
+}
+
+/// Walks every stored entity and collects the fields typed as entity references
+/// in the schema which point to entities that don't exist.
+/// References to interfaces are valid if any entity implementing the interface has the id.
+/// Derived fields are skipped, since they are always built from the stored entities.
+/// The result is sorted by entity type, id and field name.
+pub(crate) fn dangling_references(store: &Store) -> Vec<DanglingReference> {
+    let mut entity_types: Vec<&String> = store.keys().collect();
+    entity_types.sort();
+
+    let mut dangling = vec![];
+    for entity_type in entity_types {
+        let definition = match get_entity_definition(entity_type) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        let reference_fields: Vec<(&String, &String, Vec<&str>)> = definition
+            .fields
+            .iter()
+            .filter(|f| !f.is_derived())
+            .filter_map(|f| {
+                let target_type = named_type(&f.field_type);
+                let candidates = entity_types_of(target_type);
+                if candidates.is_empty() {
+                    None
+                } else {
+                    Some((&f.name, target_type, candidates))
+                }
+            })
+            .collect();
+
+        let entities = &store[entity_type];
+        let mut ids: Vec<&String> = entities.keys().collect();
+        ids.sort();
+
+        for id in ids {
+            for (field, target_type, candidates) in reference_fields.iter() {
+                let value = match entities[id].get(*field) {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                for target_id in linked_ids(value) {
+                    let exists = candidates.iter().any(|candidate| {
+                        store
+                            .get(*candidate)
+                            .map_or(false, |entities| entities.contains_key(&target_id))
+                    });
+                    if !exists {
+                        dangling.push(DanglingReference {
+                            entity_type: entity_type.clone(),
+                            id: id.clone(),
+                            field: (*field).clone(),
+                            target_type: (*target_type).clone(),
+                            target_id,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    dangling
+}
+
+/// Returns the name of the type a field holds, without the list and non-null wrappers.
+fn named_type<'a>(field_type: &'a schema::Type<'static, String>) -> &'a String {
+    match field_type {
+        schema::Type::NamedType(name) => name,
+        schema::Type::ListType(inner) | schema::Type::NonNullType(inner) => named_type(inner),
+    }
+}
+
+/// Returns the entity types a reference to `type_name` can point to: the entity type itself,
+/// or all entity types implementing it if it's an interface. Scalars and enums have none.
+fn entity_types_of(type_name: &str) -> Vec<&'static str> {
+    SCHEMA
+        .definitions
+        .iter()
+        .filter_map(|def| match def {
+            schema::Definition::TypeDefinition(schema::TypeDefinition::Object(o))
+                if o.name == type_name
+                    || o.implements_interfaces.iter().any(|i| i == type_name) =>
+            {
+                Some(o.name.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use graph::data::store::Value;
+
+use crate::context::conversion::get_json_value;
+
+/// Number of unchanged lines shown around every change in a snapshot diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// Serializes the store into pretty printed JSON with typed values.
+/// Entity types, ids and fields are sorted, so the same store always produces the same output.
+pub(crate) fn serialize_store(
+    store: &HashMap<String, HashMap<String, HashMap<String, Value>>>,
+) -> String {
+    let store: BTreeMap<&String, BTreeMap<&String, BTreeMap<&String, serde_json::Value>>> = store
+        .iter()
+        .map(|(entity_type, entities)| {
+            let entities = entities
+                .iter()
+                .map(|(id, entity)| {
+                    let fields = entity
+                        .iter()
+                        .map(|(field, value)| (field, get_json_value(value)))
+                        .collect();
+                    (id, fields)
+                })
+                .collect();
+            (entity_type, entities)
+        })
+        .collect();
+
+    let mut serialized = serde_json::to_string_pretty(&store)
+        .expect("Serializing the store to JSON should not fail");
+    serialized.push('\n');
+    serialized
+}
+
+/// Returns the location of a named snapshot of a test file:
+/// `<test file folder>/__snapshots__/<test file name>/<snapshot name>.json`.
+pub(crate) fn snapshot_path(test_file: &Path, name: &str) -> PathBuf {
+    test_file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("__snapshots__")
+        .join(test_file_name(test_file))
+        .join(sanitize_file_name(name))
+        .with_extension("json")
+}
+
+/// Returns the name of a test file without the `.test.ts` extension.
+pub(crate) fn test_file_name(test_file: &Path) -> &str {
+    test_file
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .trim_end_matches(".ts")
+        .trim_end_matches(".test")
+}
+
+/// Replaces all characters that may not be safe to use in a file name with `_`.
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Returns a copy of the store with only the entities of `entity_type`
+/// and/or the entities which ids start with `id_prefix`.
+pub(crate) fn filter_store(
+    store: &HashMap<String, HashMap<String, HashMap<String, Value>>>,
+    entity_type: Option<&str>,
+    id_prefix: Option<&str>,
+) -> HashMap<String, HashMap<String, HashMap<String, Value>>> {
+    store
+        .iter()
+        .filter(|(t, _)| entity_type.map_or(true, |entity_type| *t == entity_type))
+        .map(|(t, entities)| {
+            let entities = entities
+                .iter()
+                .filter(|(id, _)| id_prefix.map_or(true, |prefix| id.starts_with(prefix)))
+                .map(|(id, entity)| (id.clone(), entity.clone()))
+                .collect();
+            (t.clone(), entities)
+        })
+        .collect()
+}
+
+/// Builds a line-by-line diff between the expected and the actual content.
+/// Only changed lines and a few lines around them are shown, removed lines are
+/// prefixed with `-` and added lines with `+`.
+pub(crate) fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let changes = diff_lines(&expected, &actual);
+
+    let changed: Vec<usize> = changes
+        .iter()
+        .enumerate()
+        .filter(|(_, change)| !matches!(change, Change::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut output: Vec<String> = vec![];
+    let mut last_shown: Option<usize> = None;
+    for (i, change) in changes.iter().enumerate() {
+        let visible = changed
+            .iter()
+            .any(|&c| i + DIFF_CONTEXT >= c && i <= c + DIFF_CONTEXT);
+        if !visible {
+            continue;
+        }
+
+        if last_shown.map_or(i > 0, |last| i > last + 1) {
+            output.push("...".dimmed().to_string());
+        }
+        last_shown = Some(i);
+
+        output.push(match change {
+            Change::Same(line) => format!("  {}", line),
+            Change::Removed(line) => format!("- {}", line).red().to_string(),
+            Change::Added(line) => format!("+ {}", line).green().to_string(),
+        });
+    }
+
+    if last_shown.map_or(false, |last| last + 1 < changes.len()) {
+        output.push("...".dimmed().to_string());
+    }
+
+    output.join("\n")
+}
+
+enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Computes the changes between two lists of lines using their longest common subsequence.
+/// The common prefix and suffix are skipped, so only the changed part of the files
+/// goes through the quadratic table.
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<Change<'a>> {
+    let prefix = expected
+        .iter()
+        .zip(actual.iter())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+
+    let removed = &expected[prefix..expected.len() - suffix];
+    let added = &actual[prefix..actual.len() - suffix];
+
+    // lcs[i][j] is the length of the longest common subsequence of removed[i..] and added[j..]
+    let mut lcs = vec![vec![0usize; added.len() + 1]; removed.len() + 1];
+    for i in (0..removed.len()).rev() {
+        for j in (0..added.len()).rev() {
+            lcs[i][j] = if removed[i] == added[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes: Vec<Change> = expected[..prefix]
+        .iter()
+        .copied()
+        .map(Change::Same)
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < removed.len() || j < added.len() {
+        if i < removed.len() && j < added.len() && removed[i] == added[j] {
+            changes.push(Change::Same(removed[i]));
+            i += 1;
+            j += 1;
+        } else if j == added.len() || (i < removed.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(Change::Removed(removed[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(added[j]));
+            j += 1;
+        }
+    }
+    changes.extend(
+        expected[expected.len() - suffix..]
+            .iter()
+            .copied()
+            .map(Change::Same),
+    );
+
+    changes
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+
+    #[test]
+    fn serialize_store_is_sorted_and_typed() {
+        let mut entity = HashMap::new();
+        entity.insert("name".to_owned(), Value::String("alice".to_owned()));
+        entity.insert("age".to_owned(), Value::Int(30));
+        let mut entities = HashMap::new();
+        entities.insert("b".to_owned(), entity.clone());
+        entities.insert("a".to_owned(), entity);
+        let mut store = HashMap::new();
+        store.insert("User".to_owned(), entities);
+
+        let serialized = serialize_store(&store);
+
+        assert!(serialized.find("\"a\"").unwrap() < serialized.find("\"b\"").unwrap());
+        assert!(serialized.find("\"age\"").unwrap() < serialized.find("\"name\"").unwrap());
+        assert!(serialized.contains("\"Int\": 30"));
+        assert!(serialized.contains("\"String\": \"alice\""));
+        assert_eq!(serialized, serialize_store(&store));
+    }
+
+    #[test]
+    fn snapshot_path_is_next_to_the_test_file() {
+        let path = snapshot_path(Path::new("tests/gravity/gravity.test.ts"), "after handler");
+
+        assert_eq!(
+            path,
+            PathBuf::from("tests/gravity/__snapshots__/gravity/after_handler.json")
+        );
+    }
+
+    #[test]
+    fn line_diff_shows_only_changes_and_context() {
+        let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let actual = "1\n2\n3\n4\n5\nsix\n7\n8\n9\n10\n";
+
+        // The colors depend on the terminal, only the lines are compared
+        let colors = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        assert_eq!(
+            colors.replace_all(&line_diff(expected, actual), ""),
+            "...\n  3\n  4\n  5\n- 6\n+ six\n  7\n  8\n  9\n..."
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::anyhow;
+use graph::data::store::Value;
+
+/// The kind of a store call made by the code under test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OperationKind {
+    Set,
+    Remove,
+    Get,
+}
+
+impl std::str::FromStr for OperationKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind.to_ascii_lowercase().as_str() {
+            "set" => Ok(OperationKind::Set),
+            "remove" => Ok(OperationKind::Remove),
+            "get" => Ok(OperationKind::Get),
+            _ => Err(anyhow!(
+                "Unknown store operation `{}`, expected one of: set, remove, get",
+                kind
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationKind::Set => write!(f, "store.set"),
+            OperationKind::Remove => write!(f, "store.remove"),
+            OperationKind::Get => write!(f, "store.get"),
+        }
+    }
+}
+
+/// A `store.set`, `store.remove` or `store.get` call recorded by the mock store.
+#[derive(Clone, Debug)]
+pub(crate) struct StoreOperation {
+    pub(crate) kind: OperationKind,
+    pub(crate) entity_type: String,
+    pub(crate) id: String,
+    /// The fields which values were changed by a `store.set`, sorted by name
+    pub(crate) changed_fields: Vec<String>,
+}
+
+impl StoreOperation {
+    /// Checks whether the operation is of the passed kind and type,
+    /// an empty id matches the operations on all entities of the type.
+    pub(crate) fn matches(&self, kind: OperationKind, entity_type: &str, id: &str) -> bool {
+        self.kind == kind && self.entity_type == entity_type && (id.is_empty() || self.id == id)
+    }
+}
+
+impl fmt::Display for StoreOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}('{}', '{}')", self.kind, self.entity_type, self.id)?;
+        if self.kind == OperationKind::Set {
+            write!(f, " changed [{}]", self.changed_fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the names of the fields of `data` which differ from the stored entity, sorted by name.
+/// All fields are changed if the entity is not stored yet.
+pub(crate) fn changed_fields(
+    stored: Option<&HashMap<String, Value>>,
+    data: &HashMap<String, Value>,
+) -> Vec<String> {
+    let mut fields: Vec<String> = data
+        .iter()
+        .filter(|(field, value)| stored.and_then(|stored| stored.get(*field)) != Some(*value))
+        .map(|(field, _)| field.clone())
+        .collect();
+    fields.sort();
+    fields
+}
+
+/// Formats the recorded operations as a numbered list, one operation per line.
+pub(crate) fn format_operations(operations: &[StoreOperation]) -> String {
+    if operations.is_empty() {
+        return "No store operations were recorded.".to_owned();
+    }
+
+    operations
+        .iter()
+        .enumerate()
+        .map(|(i, operation)| format!("{}. {}", i + 1, operation))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -303,9 +303,79 @@ impl<C: Blockchain> MatchstickInstance<C> {
             return_value_ptr,
             reverts
         );
+        link!(
+            "mockFunctionOnce",
+            mock_function_once,
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+            return_value_ptr,
+            reverts
+        );
+        link!(
+            "mockFunctionRevert",
+            mock_function_revert,
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+            kind_ptr,
+            data_ptr,
+            once
+        );
+        link!(
+            "mockFunctionWhenExhausted",
+            mock_function_when_exhausted,
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+            behavior_ptr
+        );
+        link!(
+            "mockFunctionWithMatchers",
+            mock_function_with_matchers,
+            contract_address_ptr,
+            fn_name_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+            arg_matchers_ptr,
+            return_value_ptr,
+            reverts
+        );
+
+        link!("logContractCalls", log_contract_calls,);
+        link!("logUnusedMocks", log_unused_mocks,);
+        link!("logDataSources", log_data_sources,);
 
         link!("clearStore", clear_store,);
+        link!("startBlock", start_block, number, hash_ptr);
+        link!("commitBlock", commit_block,);
+        link!("revertToBlock", revert_to_block, number);
+        link!(
+            "storeGetAtBlock",
+            store_get_at_block,
+            entity_type_ptr,
+            id_ptr,
+            number
+        );
+        link!("blockHash", block_hash, number);
+        link!("logStoreOperations", log_store_operations,);
+        link!("clearStoreOperations", clear_store_operations,);
         link!("logStore", log_store,);
+        link!("loadFixture", load_fixture_file, path_ptr);
+        link!("loadMockFixture", load_mock_fixture_file, path_ptr);
+        link!("graphqlQuery", graphql_query, query_ptr, variables_ptr);
+        link!(
+            "dumpStore",
+            dump_store,
+            path_ptr,
+            entity_type_ptr,
+            id_prefix_ptr
+        );
+        link!("saveStore", save_store, name_ptr);
+        link!("restoreStore", restore_store, name_ptr);
         link!(
             "store.get",
             mock_store_get,
@@ -432,6 +502,16 @@ impl<C: Blockchain> MatchstickInstance<C> {
             network_ptr,
             context_ptr
         );
+        link!(
+            "dataSourceMock.useCreated",
+            use_created_data_source,
+            template_ptr,
+            address_ptr
+        );
+        link!(
+            "dataSourceMock.resetValues",
+            reset_data_source_return_values,
+        );
 
         link!("ens.nameByHash", wasm_ctx.ens_name_by_hash, ptr);
 
@@ -459,6 +539,12 @@ impl<C: Blockchain> MatchstickInstance<C> {
 
         link!("_registerHook", register_hook, func_idx, role);
 
+        link!(
+            "_assert.withMessage",
+            set_assertion_message,
+            message_ptr,
+            call_site_ptr
+        );
         link!(
             "_assert.fieldEquals",
             assert_field_equals,
@@ -468,6 +554,83 @@ impl<C: Blockchain> MatchstickInstance<C> {
             expected_val_ptr
         );
         link!("_assert.equals", assert_equals, expected_ptr, actual_ptr);
+        link!(
+            "_assert.entityEquals",
+            assert_entity_equals,
+            entity_type_ptr,
+            id_ptr,
+            expected_ptr
+        );
+        link!(
+            "_assert.entityCount",
+            assert_entity_count,
+            entity_type_ptr,
+            expected_count
+        );
+        link!(
+            "_assert.entityExists",
+            assert_entity_exists,
+            entity_type_ptr,
+            id_ptr
+        );
+        link!(
+            "_assert.entityWithFields",
+            assert_entity_with_fields,
+            entity_type_ptr,
+            fields_ptr
+        );
+        link!(
+            "_assert.storeOperationCount",
+            assert_store_operation_count,
+            operation_ptr,
+            entity_type_ptr,
+            id_ptr,
+            expected_count
+        );
+        link!(
+            "_assert.contractCallCount",
+            assert_contract_call_count,
+            contract_address_ptr,
+            fn_signature_ptr,
+            fn_args_ptr,
+            expected_count
+        );
+        link!(
+            "_assert.contractNotCalled",
+            assert_contract_not_called,
+            contract_address_ptr,
+            fn_signature_ptr
+        );
+        link!(
+            "_assert.contractCallReverted",
+            assert_contract_call_reverted,
+            contract_address_ptr,
+            fn_signature_ptr,
+            reason_ptr
+        );
+        link!(
+            "_assert.dataSourceCreated",
+            assert_data_source_created,
+            template_ptr,
+            params_ptr
+        );
+        link!(
+            "_assert.dataSourceCreatedWithContext",
+            assert_data_source_created_with_context,
+            template_ptr,
+            params_ptr,
+            context_ptr
+        );
+        link!(
+            "_assert.dataSourceCount",
+            assert_data_source_count,
+            template_ptr,
+            expected_count
+        );
+        link!(
+            "_assert.noDanglingReferences",
+            assert_no_dangling_references,
+        );
         link!(
             "_assert.notInStore",
             assert_not_in_store,
@@ -475,6 +638,19 @@ impl<C: Blockchain> MatchstickInstance<C> {
             id_ptr
         );
 
+        link!(
+            "_assert.queryResult",
+            assert_query_result,
+            query_ptr,
+            variables_ptr,
+            expected_ptr
+        );
+        link!(
+            "_assert.storeMatchesSnapshot",
+            assert_store_matches_snapshot,
+            name_ptr
+        );
+
         link!("countEntities", count_entities, entity_type);
 
         // Linking gas function
@@ -9,6 +9,7 @@ use graph_chain_ethereum::Chain;
 
 use crate::compiler::Compiler;
 use crate::config::MatchstickConfig;
+use crate::context::Cassette;
 use crate::instance::MatchstickInstance;
 use crate::test_suite::{Test, TestGroup, TestResult, Testable};
 
@@ -33,6 +34,12 @@ thread_local! {
     pub(crate) static SCHEMA_LOCATION: RefCell<PathBuf> = RefCell::new(PathBuf::new());
     pub(crate) static TESTS_LOCATION: RefCell<PathBuf> = RefCell::new(PathBuf::new());
     pub(crate) static LIBS_LOCATION: RefCell<PathBuf> = RefCell::new(PathBuf::new());
+    pub(crate) static ARTIFACTS_LOCATION: RefCell<Option<PathBuf>> = RefCell::new(None);
+    pub(crate) static UPDATE_SNAPSHOTS: RefCell<bool> = RefCell::new(false);
+    pub(crate) static STRICT_REFERENCES: RefCell<bool> = RefCell::new(false);
+    pub(crate) static STRICT_MOCKS: RefCell<bool> = RefCell::new(false);
+    pub(crate) static NETWORKS_LOCATION: RefCell<PathBuf> = RefCell::new(PathBuf::new());
+    pub(crate) static NETWORK: RefCell<Option<String>> = RefCell::new(None);
 }
 
 fn main() {
@@ -55,6 +62,29 @@ fn main() {
     });
     TESTS_LOCATION.with(|path| *path.borrow_mut() = PathBuf::from(&config.tests_path));
     LIBS_LOCATION.with(|path| *path.borrow_mut() = PathBuf::from(&config.libs_path));
+    ARTIFACTS_LOCATION.with(|path| {
+        // The stores of failed tests are dumped only if an artifacts folder is configured,
+        // `--dump-failed` dumps them to the `.artifacts` folder inside the tests folder by default.
+        *path.borrow_mut() = config
+            .artifacts_path
+            .clone()
+            .or_else(|| {
+                matches
+                    .is_present("dump_failed")
+                    .then(|| format!("{}/.artifacts", config.tests_path.trim_end_matches('/')))
+            })
+            .map(PathBuf::from)
+    });
+    UPDATE_SNAPSHOTS.with(|update| *update.borrow_mut() = matches.is_present("update_snapshots"));
+    STRICT_REFERENCES.with(|strict| *strict.borrow_mut() = config.strict_references);
+    STRICT_MOCKS.with(|strict| *strict.borrow_mut() = config.strict_mocks);
+    NETWORKS_LOCATION.with(|path| *path.borrow_mut() = PathBuf::from(&config.networks_path));
+    NETWORK.with(|network| {
+        *network.borrow_mut() = matches
+            .value_of("network")
+            .map(|network| network.to_owned())
+            .or_else(|| config.network.clone())
+    });
 
     logging::log_with_style!(bright_green, "Compiling...\n");
 
@@ -76,13 +106,56 @@ fn main() {
     let ms_instances: HashMap<String, MatchstickInstance<Chain>> = outputs
         .into_iter()
         .map(|(key, val)| {
-            (
-                key,
-                MatchstickInstance::<Chain>::new(val.file.to_str().unwrap()),
-            )
+            let instance = MatchstickInstance::<Chain>::new(val.file.to_str().unwrap());
+            instance.instance_ctx_mut().set_test_file(val.source);
+
+            (key, instance)
         })
         .collect();
 
+    // Loads the global fixtures from the config into the store of every test suite
+    // and mocks the contract calls from the mock fixtures. The fixtures are loaded
+    // again before every test, so `clearStore()` in an `afterEach` hook doesn't wipe them.
+    for (name, instance) in ms_instances.iter() {
+        instance.instance_ctx_mut().fixtures = config.fixtures.clone();
+        for path in config.fixtures.iter() {
+            instance
+                .instance_ctx_mut()
+                .load_fixture(path)
+                .unwrap_or_else(|err| {
+                    logging::critical!("Could not load fixture for {}: {:#}", name, err)
+                });
+        }
+        for path in config.mock_fixtures.iter() {
+            instance
+                .instance_ctx_mut()
+                .load_mock_fixture(path)
+                .unwrap_or_else(|err| {
+                    logging::critical!("Could not load mock fixture for {}: {:#}", name, err)
+                });
+        }
+    }
+
+    // Contract calls which are not mocked are answered from the cassette, or recorded
+    // from the JSON-RPC node with `--record-calls`.
+    let record_calls = matches.is_present("record_calls");
+    if record_calls && config.rpc_url.is_none() {
+        logging::critical!("Recording contract calls requires `rpcUrl` in matchstick.yaml");
+    }
+    if record_calls || PathBuf::from(&config.cassette_path).exists() {
+        let rpc_url = config.rpc_url.clone().filter(|_| record_calls);
+        let cassette = Cassette::load(
+            &PathBuf::from(&config.cassette_path),
+            rpc_url,
+            config.rpc_block,
+        )
+        .unwrap_or_else(|err| logging::critical!("Could not load the cassette: {:#}", err));
+
+        for instance in ms_instances.values() {
+            instance.instance_ctx_mut().cassette = Some(cassette.clone());
+        }
+    }
+
     // A test suite abstraction for each instance.
     let test_suites: HashMap<String, TestGroup> = ms_instances
         .iter()
@@ -3,6 +3,17 @@ use std::collections::HashMap;
 
 use crate::logging;
 
+/// A data source declared under dataSources in the subgraph.yaml
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestDataSource {
+    pub name: String,
+    pub network: String,
+    /// The `source.address` of the data source, if declared
+    pub address: Option<String>,
+    /// The path of the mapping file, relative to the subgraph.yaml
+    pub mapping_file: String,
+}
+
 /// Parses the yaml file
 /// If the parsing fails returns a serde Value containing an empty String
 pub fn parse_yaml(path: &str) -> Value {
@@ -43,6 +54,21 @@ pub fn extract_string_or(value: &Value, key: &str, default: String) -> String {
         .to_owned()
 }
 
+/// Extracts the bool value of the passed key from the parsed yaml
+/// Fallbacks to the default value if the key is missing or the value is not a bool
+pub fn extract_bool_or(value: &Value, key: &str, default: bool) -> bool {
+    value
+        .get(key)
+        .and_then(|value| value.as_bool())
+        .unwrap_or(default)
+}
+
+/// Extracts the u64 value of the passed key from the parsed yaml
+/// Returns None if the key is missing or the value is not a positive integer
+pub fn extract_u64(value: &Value, key: &str) -> Option<u64> {
+    value.get(key).and_then(|value| value.as_u64())
+}
+
 /// Extracts the value of the passed key as Sequence
 /// Will return an empty Vec if the key is missing
 /// Will panic if the value can't be parsed as Sequence
@@ -55,6 +81,20 @@ fn extract_vec(value: &Value, key: &str) -> Sequence {
         .to_vec()
 }
 
+/// Extracts the string values of the sequence under the passed key
+/// Will return an empty Vec if the key is missing
+/// Will panic if the value can't be parsed as a Sequence of strings
+pub fn extract_string_vec(value: &Value, key: &str) -> Vec<String> {
+    extract_vec(value, key)
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .unwrap_or_else(|| panic!("Couldn't parse values of `{key}` as str"))
+                .to_owned()
+        })
+        .collect()
+}
+
 /// Extracts the sources declared under dataSources or templates in the subraph.yaml
 fn parse_sources(path: &str) -> Sequence {
     let subgraph_yaml = parse_yaml(path);
@@ -93,6 +133,65 @@ pub fn collect_handlers(path: &str) -> HashMap<String, Vec<String>> {
         .collect()
 }
 
+/// Collects the data sources declared under dataSources in the subgraph.yaml,
+/// the network defaults to mainnet
+pub fn collect_data_sources(path: &str) -> Vec<ManifestDataSource> {
+    extract_vec(&parse_yaml(path), "dataSources")
+        .iter()
+        .map(|source| {
+            let mapping = source
+                .get("mapping")
+                .expect("No key 'mapping' in datasource.");
+
+            ManifestDataSource {
+                name: extract_string(source, "name"),
+                network: extract_string_or(source, "network", "mainnet".to_owned()),
+                address: source
+                    .get("source")
+                    .and_then(|source| source.get("address"))
+                    .and_then(|address| address.as_str())
+                    .map(|address| address.to_owned()),
+                mapping_file: extract_string_or(mapping, "file", "".to_owned()),
+            }
+        })
+        .collect()
+}
+
+/// Collects the name and network of the templates declared in the subgraph.yaml,
+/// the network defaults to mainnet
+pub fn collect_templates(path: &str) -> Vec<(String, String)> {
+    extract_vec(&parse_yaml(path), "templates")
+        .iter()
+        .map(|template| {
+            (
+                extract_string(template, "name"),
+                extract_string_or(template, "network", "mainnet".to_owned()),
+            )
+        })
+        .collect()
+}
+
+/// Collects the name and file of every ABI declared under `mapping.abis`
+/// in dataSources or templates, an ABI shared by several sources is listed once
+pub fn collect_abis(path: &str) -> Vec<(String, String)> {
+    let mut abis: Vec<(String, String)> = vec![];
+
+    for source in parse_sources(path).iter() {
+        let mapping = source
+            .get("mapping")
+            .expect("No key 'mapping' in datasource.");
+
+        for abi in extract_vec(mapping, "abis").iter() {
+            let name = extract_string(abi, "name");
+            if !abis.iter().any(|(other, _)| *other == name) {
+                abis.push((name, extract_string(abi, "file")));
+            }
+        }
+    }
+
+    abis
+}
+
 /// Extracts the schema location from subraph.yaml
 /// Will panic if the `schema` or `file` key is missing
 pub fn get_schema_location(path: &str) -> String {
@@ -157,6 +256,21 @@ mod parser_tests {
         assert_eq!(test_folder, "./node_modules".to_owned())
     }
 
+    #[test]
+    fn extract_string_vec_returns_values_as_strings() {
+        let config_yaml = parse_yaml("mocks/yamls/matchstick.yaml");
+        let fixtures = extract_string_vec(&config_yaml, "fixtures");
+
+        assert_eq!(fixtures, vec!["./tests/fixtures/store.yaml".to_owned()])
+    }
+
+    #[test]
+    fn extract_string_vec_returns_empty_vec_when_key_is_missing() {
+        let config_yaml = parse_yaml("mocks/yamls/matchstick.yaml");
+
+        assert!(extract_string_vec(&config_yaml, "missing").is_empty())
+    }
+
     #[test]
     fn collect_handlers_returns_all_handlers() {
         let handlers = collect_handlers("mocks/yamls/subgraph.yaml");
@@ -176,6 +290,47 @@ mod parser_tests {
         assert_eq!(handlers, expected)
     }
 
+    #[test]
+    fn collect_data_sources_returns_network_address_and_mapping() {
+        let data_sources = collect_data_sources("mocks/yamls/subgraph.yaml");
+
+        assert_eq!(
+            data_sources,
+            vec![ManifestDataSource {
+                name: "Gravity".to_owned(),
+                network: "mainnet".to_owned(),
+                address: Some("0x2E645469f354BB4F5c8a05B3b30A929361cf77eC".to_owned()),
+                mapping_file: "./src/gravity.ts".to_owned(),
+            }]
+        )
+    }
+
+    #[test]
+    fn collect_templates_returns_template_names_and_networks() {
+        let templates = collect_templates("mocks/yamls/subgraph.yaml");
+
+        assert_eq!(
+            templates,
+            vec![("GraphTokenLockWallet".to_owned(), "mainnet".to_owned())]
+        )
+    }
+
+    #[test]
+    fn collect_abis_returns_abis_of_all_sources() {
+        let abis = collect_abis("mocks/yamls/subgraph.yaml");
+
+        assert_eq!(
+            abis,
+            vec![
+                ("Gravity".to_owned(), "./abis/Gravity.json".to_owned()),
+                (
+                    "GraphTokenLockWallet".to_owned(),
+                    "./abis/GraphTokenLockWallet.json".to_owned()
+                ),
+            ]
+        )
+    }
+
     #[test]
     fn collect_handlers_returns_empty_vec_if_no_handlers() {
         let handlers = collect_handlers("mocks/yamls/subgraph_no_handlers.yaml");
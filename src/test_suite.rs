@@ -1,9 +1,14 @@
 use colored::Colorize;
 use graph::blockchain::Blockchain;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::time::Instant;
 use wasmtime::Func;
 
-use crate::{instance::MatchstickInstance, logging};
+use crate::{
+    context::MatchstickInstanceContext, instance::MatchstickInstance, logging, ARTIFACTS_LOCATION,
+    STRICT_REFERENCES,
+};
 
 pub struct Test {
     pub name: String,
@@ -11,6 +16,91 @@ pub struct Test {
     func: Func,
     before_hooks: Vec<Func>,
     after_hooks: Vec<Func>,
+    context: Rc<dyn TestContext>,
+}
+
+/// Gives the tests access to the state of the instance they are running in,
+/// without having to know the type of the blockchain the instance has been created for.
+pub trait TestContext {
+    /// Called before the beforeEach hooks of the test are called.
+    fn on_test_started(&self);
+    /// Runs the checks enabled in the config on the store of a passed test,
+    /// returns false if the test should be failed.
+    fn check_store(&self) -> bool;
+    /// Called when the test function has finished, before the afterEach hooks are called.
+    fn on_test_finished(&self, test_name: &str, passed: bool);
+}
+
+impl<C: Blockchain> TestContext for RefCell<Option<MatchstickInstanceContext<C>>> {
+    fn on_test_started(&self) {
+        if let Some(context) = self.borrow_mut().as_mut() {
+            // A message set for an assertion which never ran is not shown for the next test
+            context.assertion_message = None;
+            context.store_operations.clear();
+            context.contract_calls.clear();
+            context.created_data_sources.clear();
+            context.clear_blocks();
+
+            // The fixtures are seeded again, in case an earlier test removed or changed them
+            for path in context.fixtures.clone() {
+                if let Err(err) = context.load_fixture(&path) {
+                    logging::critical!("Could not load fixture {}: {:#}", path, err);
+                }
+            }
+        }
+    }
+
+    fn check_store(&self) -> bool {
+        if !STRICT_REFERENCES.with(|strict| *strict.borrow()) {
+            return true;
+        }
+
+        let dangling = match self.borrow_mut().as_mut() {
+            Some(context) => context.dangling_references(),
+            None => return true,
+        };
+        if !dangling.is_empty() {
+            logging::error!(
+                "(strictReferences) Found {} dangling references:\n{}",
+                dangling.len(),
+                dangling
+                    .iter()
+                    .map(|reference| reference.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            );
+            return false;
+        }
+
+        true
+    }
+
+    fn on_test_finished(&self, test_name: &str, passed: bool) {
+        if passed {
+            return;
+        }
+
+        if let Some(context) = self.borrow_mut().as_mut() {
+            // The full history can be logged from the test with `logStoreOperations`,
+            // `logContractCalls` and `logDataSources`
+            if !context.store_operations.is_empty() {
+                logging::info!("Store operations:\n{}", context.format_store_operations());
+            }
+            if !context.contract_calls.is_empty() {
+                logging::info!("Contract calls:\n{}", context.format_contract_calls());
+            }
+            if !context.created_data_sources.is_empty() {
+                logging::info!("Data sources:\n{}", context.format_data_sources());
+            }
+
+            if let Some(artifacts) = ARTIFACTS_LOCATION.with(|path| path.borrow().clone()) {
+                match context.dump_failed_test(&artifacts, test_name) {
+                    Ok(path) => logging::info!("Store dumped to {}", path.display()),
+                    Err(err) => logging::warning!("Could not dump the store: {:#}", err),
+                }
+            }
+        }
+    }
 }
 
 pub struct TestResult {
@@ -35,13 +125,14 @@ pub enum Testable {
 }
 
 impl Test {
-    fn new(name: String, should_fail: bool, func: Func) -> Self {
+    fn new(name: String, should_fail: bool, func: Func, context: Rc<dyn TestContext>) -> Self {
         Test {
             name,
             should_fail,
             func,
             before_hooks: vec![],
             after_hooks: vec![],
+            context,
         }
     }
 
@@ -62,6 +153,7 @@ impl Test {
     }
 
     pub fn run(&self) -> TestResult {
+        self.context.on_test_started();
         self.before();
 
         // NOTE: Calling a test func should not fail for any other reason than:
@@ -96,6 +188,12 @@ impl Test {
             }
         };
 
+        // Tests expected to fail may stop half way through and leave the store in any state,
+        // so the store checks are run only for the tests that are expected to pass.
+        let passed = passed && (self.should_fail || self.context.check_store());
+
+        self.context.on_test_finished(&self.name, passed);
+
         // Convert the elapsed time to milliseconds
         let elapsed_in_ms = now.elapsed().as_secs_f32() * 1000.0;
 
@@ -185,6 +283,7 @@ fn build_test_group<C: graph::blockchain::Blockchain>(
                 t_name.to_string(),
                 should_fail,
                 test.clone(),
+                matchstick.instance_ctx.clone(),
             ))),
             "describe" => {
                 let nested_functions = register_describe(matchstick, t_idx);
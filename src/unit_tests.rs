@@ -1,92 +1,1291 @@
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
     use std::path::PathBuf;
     use std::str::FromStr;
     use std::sync::Once;
 
     use graph::{
-        data::store::Value,
-        prelude::ethabi::{Address, Token},
-        runtime::{asc_get, asc_new, gas::GasCounter, try_asc_get, AscPtr, AscType},
+        data::store::{scalar::Bytes, Value},
+        prelude::{
+            ethabi::{self, Address, Token},
+            BigDecimal, BigInt, Entity,
+        },
+        runtime::{
+            asc_get, asc_new, gas::GasCounter, try_asc_get, AscPtr, AscType, HostExportError,
+        },
     };
     use graph_chain_ethereum::{runtime::abi::AscUnresolvedContractCall_0_0_4, Chain};
     use graph_runtime_wasm::asc_abi::class::{
-        Array, AscEnum, AscTypedMap, AscTypedMapEntry, EnumPayload, EthereumValueKind,
-        StoreValueKind, TypedArray,
+        Array, AscEntity, AscEnum, AscString, AscTypedMap, AscTypedMapEntry, EnumPayload,
+        EthereumValueKind, StoreValueKind, TypedArray,
     };
     use serial_test::serial;
 
     use crate::{
-        context::{asc_string_from_str, MatchstickInstanceContext, REVERTS_IDENTIFIER},
+        context::{
+            asc_string_from_str, Cassette, MatchstickInstanceContext, MockResult, RevertReason,
+        },
         logging::{accum, flush, LOGS},
-        {MatchstickInstance, SCHEMA_LOCATION},
+        test_suite::TestContext,
+        {
+            MatchstickInstance, ARTIFACTS_LOCATION, MANIFEST_LOCATION, NETWORK, NETWORKS_LOCATION,
+            SCHEMA_LOCATION, STRICT_MOCKS, STRICT_REFERENCES, UPDATE_SNAPSHOTS,
+        },
     };
 
     static GET_SCHEMA: Once = Once::new();
 
-    fn get_context() -> MatchstickInstanceContext<Chain> {
+    fn get_module() -> MatchstickInstance<Chain> {
         GET_SCHEMA.call_once(|| {
             SCHEMA_LOCATION
                 .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/schema.graphql"));
         });
-        let module = <MatchstickInstance<Chain>>::new("./mocks/wasm/gravity.wasm");
+        <MatchstickInstance<Chain>>::new("./mocks/wasm/gravity.wasm")
+    }
+
+    fn get_context() -> MatchstickInstanceContext<Chain> {
+        let module = get_module();
+
+        module
+            .instance_ctx
+            .take()
+            .take()
+            .expect("Couldn't get context from module.")
+    }
+
+    fn alloc_string(
+        context: &mut MatchstickInstanceContext<Chain>,
+        value: &str,
+    ) -> AscPtr<AscString> {
+        AscPtr::alloc_obj(
+            asc_string_from_str(value),
+            &mut context.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.")
+    }
+
+    fn alloc_entity(
+        context: &mut MatchstickInstanceContext<Chain>,
+        fields: Vec<(&str, Value)>,
+    ) -> AscPtr<AscEntity> {
+        let data: HashMap<String, Value> = fields
+            .into_iter()
+            .map(|(field, value)| (field.to_owned(), value))
+            .collect();
+
+        asc_new(
+            &mut context.wasm_ctx,
+            &Entity::from(data).sorted(),
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.")
+    }
+
+    fn store_set(
+        context: &mut MatchstickInstanceContext<Chain>,
+        entity_type: &str,
+        id: &str,
+        fields: Vec<(&str, Value)>,
+    ) {
+        try_store_set(context, entity_type, id, fields).expect("Couldn't call mock_store_set.");
+    }
+
+    fn try_store_set(
+        context: &mut MatchstickInstanceContext<Chain>,
+        entity_type: &str,
+        id: &str,
+        fields: Vec<(&str, Value)>,
+    ) -> Result<(), HostExportError> {
+        let entity_type_ptr = alloc_string(context, entity_type);
+        let id_ptr = alloc_string(context, id);
+        let data_ptr = alloc_entity(context, fields);
+
+        context.mock_store_set(&GasCounter::new(), entity_type_ptr, id_ptr, data_ptr)
+    }
+
+    fn alloc_tokens(context: &mut MatchstickInstanceContext<Chain>, tokens: &[Token]) -> u32 {
+        let tokens_ptr: AscPtr<Array<AscPtr<AscEnum<EthereumValueKind>>>> =
+            asc_new(&mut context.wasm_ctx, tokens, &GasCounter::new())
+                .expect("Couldn't create pointer.");
+        tokens_ptr.wasm_ptr()
+    }
+
+    /// Calls `ethereum.call` and returns the decoded result, `None` if the call reverted.
+    fn call_contract(
+        context: &mut MatchstickInstanceContext<Chain>,
+        address: &str,
+        fn_name: &str,
+        fn_signature: &str,
+        args: &[Token],
+    ) -> Result<Option<Vec<Token>>, HostExportError> {
+        let address = Address::from_str(address).expect("Couldn't create Address.");
+        let unresolved_call = AscUnresolvedContractCall_0_0_4 {
+            contract_name: alloc_string(context, "Contract"),
+            contract_address: asc_new(&mut context.wasm_ctx, &address, &GasCounter::new())
+                .expect("Couldn't create pointer."),
+            function_name: alloc_string(context, fn_name),
+            function_signature: alloc_string(context, fn_signature),
+            function_args: AscPtr::new(alloc_tokens(context, args)),
+        };
+        let call_pointer =
+            AscPtr::alloc_obj(unresolved_call, &mut context.wasm_ctx, &GasCounter::new())
+                .expect("Couldn't create pointer.");
+
+        let result = context.ethereum_call(&GasCounter::new(), call_pointer.wasm_ptr())?;
+        if result.is_null() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            asc_get::<_, Array<AscPtr<AscEnum<EthereumValueKind>>>, _>(
+                &context.wasm_ctx,
+                result,
+                &GasCounter::new(),
+            )
+            .expect("Couldn't unwrap result."),
+        ))
+    }
+
+    #[test]
+    #[serial]
+    fn log_basic_test() {
+        let mut context = get_context();
+
+        let message = asc_string_from_str("log message");
+        let pointer = AscPtr::alloc_obj(message, &mut context.wasm_ctx, &GasCounter::new())
+            .expect("Couldn't create pointer.");
+
+        accum();
+        context
+            .log(&GasCounter::new(), 3, pointer)
+            .expect("Couldn't call log.");
+
+        unsafe {
+            assert_eq!(LOGS.len(), 1);
+            assert!(LOGS.pop().unwrap().contains("log message"));
+            flush();
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "🆘")]
+    fn log_panic_test() {
+        let mut context = get_context();
+
+        let message = asc_string_from_str("log message");
+        let pointer = AscPtr::alloc_obj(message, &mut context.wasm_ctx, &GasCounter::new())
+            .expect("Couldn't create pointer.");
+
+        context
+            .log(&GasCounter::new(), 0, pointer)
+            .expect("Couldn't call log.");
+    }
+
+    #[test]
+    #[serial]
+    fn clear_store_basic_test() {
+        let mut context = get_context();
+
+        context.store.insert("type".to_owned(), HashMap::new());
+
+        context
+            .clear_store(&GasCounter::new())
+            .expect("Couldn't call clear_store");
+
+        assert_eq!(context.store.len(), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn save_and_restore_store_test() {
+        let mut context = get_context();
+
+        let mut gravatars = HashMap::new();
+        gravatars.insert("gravatar1".to_owned(), HashMap::new());
+        context.store.insert("Gravatar".to_owned(), gravatars);
+        context.fn_ret_map.insert(
+            "fn_id".to_owned(),
+            MockResult::Return(vec![Token::Bool(true)]),
+        );
+
+        let name_ptr = alloc_string(&mut context, "fixture");
+        context
+            .save_store(&GasCounter::new(), name_ptr)
+            .expect("Couldn't call save_store.");
+
+        context
+            .clear_store(&GasCounter::new())
+            .expect("Couldn't call clear_store.");
+        context.fn_ret_map.clear();
+        context
+            .store
+            .insert("Transaction".to_owned(), HashMap::new());
+
+        context
+            .restore_store(&GasCounter::new(), name_ptr)
+            .expect("Couldn't call restore_store.");
+
+        assert_eq!(context.store.len(), 1);
+        assert!(context
+            .store
+            .get("Gravatar")
+            .unwrap()
+            .contains_key("gravatar1"));
+        assert_eq!(
+            context.fn_ret_map.get("fn_id"),
+            Some(&MockResult::Return(vec![Token::Bool(true)]))
+        );
+
+        let unknown_ptr = alloc_string(&mut context, "unknown");
+        assert!(context
+            .restore_store(&GasCounter::new(), unknown_ptr)
+            .is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn load_fixture_converts_values_and_links_derived_fields() {
+        let mut context = get_context();
+
+        let path_ptr = alloc_string(&mut context, "mocks/fixtures/store.yaml");
+        context
+            .load_fixture_file(&GasCounter::new(), path_ptr)
+            .expect("Couldn't call load_fixture_file.");
+
+        let pool = context.store.get("Pool").unwrap().get("pool1").unwrap();
+        assert_eq!(
+            pool.get("price"),
+            Some(&Value::BigDecimal(BigDecimal::from_str("1.5").unwrap()))
+        );
+        assert_eq!(
+            pool.get("liquidity"),
+            Some(&Value::BigInt(
+                BigInt::from_str("100000000000000000000").unwrap()
+            ))
+        );
+        assert_eq!(pool.get("fee"), Some(&Value::Int(3000)));
+        assert_eq!(pool.get("active"), Some(&Value::Bool(true)));
+        assert_eq!(
+            pool.get("token"),
+            Some(&Value::Bytes(Bytes::from_str("0x1234").unwrap()))
+        );
+        assert_eq!(
+            pool.get("revocable"),
+            Some(&Value::String("Enabled".to_owned()))
+        );
+        assert_eq!(
+            pool.get("tags"),
+            Some(&Value::List(vec![
+                Value::String("stable".to_owned()),
+                Value::String("verified".to_owned()),
+            ]))
+        );
+
+        let account = context.store.get("Account").unwrap().get("0xabcd").unwrap();
+        assert_eq!(
+            account.get("transfers"),
+            Some(&Value::List(vec![
+                Value::String("transfer1".to_owned()),
+                Value::String("transfer2".to_owned()),
+            ]))
+        );
+
+        context
+            .load_fixture("mocks/fixtures/tokens.json")
+            .expect("Couldn't load JSON fixture.");
+
+        let tokens = context.store.get("Token").unwrap();
+        assert_eq!(
+            tokens.get("9000000000").unwrap().get("id"),
+            Some(&Value::BigInt(BigInt::from(9000000000_i64)))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn load_fixture_returns_error_for_invalid_values() {
+        let mut context = get_context();
+
+        let err = context
+            .load_fixture("mocks/fixtures/invalid_enum.yaml")
+            .expect_err("Fixture with an invalid enum value should fail.");
+
+        assert!(format!("{:#}", err).contains("is not a value of enum `Revocability`"));
+        assert!(context.store.get("Pool").is_none());
+        assert!(context.load_fixture("mocks/fixtures/missing.yaml").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn load_mock_fixture_mocks_all_calls() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let mut context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+
+        let path_ptr = alloc_string(&mut context, "mocks/fixtures/mocks.yaml");
+        context
+            .load_mock_fixture_file(&GasCounter::new(), path_ptr)
+            .expect("Couldn't call load_mock_fixture_file.");
+        assert_eq!(context.fn_ret_map.len(), 5);
+
+        let contract = "0x2E645469f354BB4F5c8a05B3b30A929361cf77eC";
+        let owner = Token::Address(Address::from_low_u64_be(1));
+        let result = call_contract(
+            &mut context,
+            contract,
+            "getGravatar",
+            "getGravatar(address):(string,string)",
+            &[owner.clone()],
+        )
+        .expect("Couldn't call ethereum_call.");
+        assert_eq!(
+            result,
+            Some(vec![
+                Token::String("Gravatar 1".to_owned()),
+                Token::String("https://example.com/1.png".to_owned()),
+            ])
+        );
+
+        let result = call_contract(
+            &mut context,
+            contract,
+            "gravatarToOwner",
+            "gravatarToOwner(uint256):(address)",
+            &[Token::Uint(2u64.into())],
+        )
+        .expect("Couldn't call ethereum_call.");
+        assert_eq!(result, None);
+        assert_eq!(
+            context.contract_calls.last().unwrap().revert,
+            Some(RevertReason::Unknown)
+        );
+
+        // The signature of `balanceOf` is derived from the manifest ABIs
+        let result = call_contract(
+            &mut context,
+            contract,
+            "balanceOf",
+            "balanceOf(address,uint256):(uint256)",
+            &[owner, Token::Uint(5u64.into())],
+        )
+        .expect("Couldn't call ethereum_call.");
+        assert_eq!(
+            result,
+            Some(vec![Token::Uint(
+                graph::prelude::ethabi::Uint::from_dec_str("1000000000000000000000").unwrap()
+            )])
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn load_mock_fixture_returns_error_for_invalid_values() {
+        let mut context = get_context();
+
+        let err = context
+            .load_mock_fixture("mocks/fixtures/invalid_mocks.json")
+            .expect_err("Mock fixture with a negative uint should fail.");
+
+        assert!(format!("{:#}", err).contains("Invalid value at position 1 of the arguments"));
+        assert!(context.fn_ret_map.is_empty());
+
+        let err = context
+            .load_mock_fixture("mocks/fixtures/revert_and_returns_mocks.json")
+            .expect_err("Mock fixture with a call which reverts and returns should fail.");
+        assert!(format!("{:#}", err).contains("either `revert` or have `returns` values"));
+        assert!(context.fn_ret_map.is_empty());
+        assert!(context
+            .load_mock_fixture("mocks/fixtures/mocks.yaml")
+            .is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn assert_store_matches_snapshot_test() {
+        let mut context = get_context();
+        let tests_folder = std::env::temp_dir().join("matchstick_snapshot_test");
+        let _ = std::fs::remove_dir_all(&tests_folder);
+        context.set_test_file(tests_folder.join("gravity.test.ts"));
+
+        store_set(
+            &mut context,
+            "Token",
+            "1",
+            vec![("symbol", Value::String("GRT".to_owned()))],
+        );
+        let name_ptr = alloc_string(&mut context, "tokens");
+
+        // The first run writes the snapshot
+        assert!(context
+            .assert_store_matches_snapshot(&GasCounter::new(), name_ptr)
+            .expect("Couldn't call assert_store_matches_snapshot."));
+        let snapshot_file = tests_folder.join("__snapshots__/gravity/tokens.json");
+        assert!(snapshot_file.exists());
+        assert!(context
+            .assert_store_matches_snapshot(&GasCounter::new(), name_ptr)
+            .expect("Couldn't call assert_store_matches_snapshot."));
+
+        store_set(
+            &mut context,
+            "Token",
+            "1",
+            vec![("symbol", Value::String("ETH".to_owned()))],
+        );
+        assert!(!context
+            .assert_store_matches_snapshot(&GasCounter::new(), name_ptr)
+            .expect("Couldn't call assert_store_matches_snapshot."));
+
+        UPDATE_SNAPSHOTS.with(|update| *update.borrow_mut() = true);
+        let updated = context.assert_store_matches_snapshot(&GasCounter::new(), name_ptr);
+        UPDATE_SNAPSHOTS.with(|update| *update.borrow_mut() = false);
+
+        assert!(updated.expect("Couldn't call assert_store_matches_snapshot."));
+        assert!(std::fs::read_to_string(&snapshot_file)
+            .unwrap()
+            .contains("\"String\": \"ETH\""));
+
+        std::fs::remove_dir_all(&tests_folder).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn dump_store_filters_entities() {
+        let mut context = get_context();
+        let dump_folder = std::env::temp_dir().join("matchstick_dump_test");
+        let _ = std::fs::remove_dir_all(&dump_folder);
+
+        store_set(
+            &mut context,
+            "Token",
+            "1",
+            vec![("symbol", Value::String("GRT".to_owned()))],
+        );
+        store_set(
+            &mut context,
+            "Token",
+            "20",
+            vec![("symbol", Value::String("ETH".to_owned()))],
+        );
+        store_set(&mut context, "Account", "0xabcd", vec![]);
+
+        let dump_file = dump_folder.join("tokens.json");
+        let path_ptr = alloc_string(&mut context, dump_file.to_str().unwrap());
+        let entity_type_ptr = alloc_string(&mut context, "Token");
+        let id_prefix_ptr = alloc_string(&mut context, "2");
+        context
+            .dump_store(&GasCounter::new(), path_ptr, entity_type_ptr, id_prefix_ptr)
+            .expect("Couldn't call dump_store.");
+
+        let dump: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&dump_file).unwrap()).unwrap();
+        assert_eq!(
+            dump,
+            serde_json::json!({
+                "Token": {
+                    "20": {
+                        "id": { "BigInt": "20" },
+                        "symbol": { "String": "ETH" }
+                    }
+                }
+            })
+        );
+
+        std::fs::remove_dir_all(&dump_folder).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn failed_test_dumps_store_to_artifacts() {
+        let module = get_module();
+        let artifacts_folder = std::env::temp_dir().join("matchstick_artifacts_test");
+        let _ = std::fs::remove_dir_all(&artifacts_folder);
+        module
+            .instance_ctx_mut()
+            .set_test_file(PathBuf::from("tests/gravity.test.ts"));
+
+        store_set(
+            &mut module.instance_ctx_mut(),
+            "Token",
+            "1",
+            vec![("symbol", Value::String("GRT".to_owned()))],
+        );
+
+        // Without an artifacts folder the stores of failed tests are not dumped
+        module.instance_ctx.on_test_finished("failing test", false);
+        assert!(!artifacts_folder.exists());
+
+        ARTIFACTS_LOCATION.with(|path| *path.borrow_mut() = Some(artifacts_folder.clone()));
+        module.instance_ctx.on_test_finished("passing test", true);
+        assert!(!artifacts_folder.exists());
+
+        module.instance_ctx.on_test_finished("failing test", false);
+        let dump = std::fs::read_to_string(artifacts_folder.join("gravity/failing_test.json"))
+            .expect("Store should be dumped for a failed test.");
+        assert!(dump.contains("\"String\": \"GRT\""));
+
+        ARTIFACTS_LOCATION.with(|path| *path.borrow_mut() = None);
+        std::fs::remove_dir_all(&artifacts_folder).unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn assert_no_dangling_references_reports_missing_entities() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Account",
+            "0xabcd",
+            vec![("id", Value::Bytes(Bytes::from_str("0xabcd").unwrap()))],
+        );
+        store_set(
+            &mut context,
+            "Vault",
+            "vault1",
+            vec![
+                ("id", Value::from("vault1")),
+                (
+                    "owners",
+                    Value::List(vec![
+                        Value::Bytes(Bytes::from_str("0xabcd").unwrap()),
+                        Value::Bytes(Bytes::from_str("0x1234").unwrap()),
+                    ]),
+                ),
+            ],
+        );
+        store_set(
+            &mut context,
+            "Wallet",
+            "wallet1",
+            vec![
+                ("id", Value::from("wallet1")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+
+        let dangling: Vec<String> = context
+            .dangling_references()
+            .iter()
+            .map(|reference| reference.to_string())
+            .collect();
+        assert_eq!(
+            dangling,
+            vec![
+                "Vault 'vault1' field 'owners' references Account '0x1234', which is not in the store",
+                "Wallet 'wallet1' field 'owner' references Owner 'owner1', which is not in the store",
+            ]
+        );
+        let result = context
+            .assert_no_dangling_references(&GasCounter::new())
+            .expect("Couldn't call assert_no_dangling_references.");
+        assert!(!result);
+
+        store_set(
+            &mut context,
+            "Account",
+            "0x1234",
+            vec![("id", Value::Bytes(Bytes::from_str("0x1234").unwrap()))],
+        );
+        store_set(
+            &mut context,
+            "Owner",
+            "owner1",
+            vec![("id", Value::from("owner1"))],
+        );
+
+        let result = context
+            .assert_no_dangling_references(&GasCounter::new())
+            .expect("Couldn't call assert_no_dangling_references.");
+        assert!(result);
+    }
+
+    #[test]
+    #[serial]
+    fn strict_references_fails_tests_with_dangling_references() {
+        let module = get_module();
+        store_set(
+            &mut module.instance_ctx_mut(),
+            "Wallet",
+            "wallet1",
+            vec![
+                ("id", Value::from("wallet1")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+
+        assert!(module.instance_ctx.check_store());
+
+        STRICT_REFERENCES.with(|strict| *strict.borrow_mut() = true);
+        assert!(!module.instance_ctx.check_store());
+
+        store_set(
+            &mut module.instance_ctx_mut(),
+            "Owner",
+            "owner1",
+            vec![("id", Value::from("owner1"))],
+        );
+        assert!(module.instance_ctx.check_store());
+        STRICT_REFERENCES.with(|strict| *strict.borrow_mut() = false);
+    }
+
+    #[test]
+    #[serial]
+    fn assert_entity_equals_reports_all_differences() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Pool",
+            "pool1",
+            vec![
+                (
+                    "price",
+                    Value::BigDecimal(BigDecimal::from_str("1").unwrap()),
+                ),
+                ("liquidity", Value::BigInt(BigInt::from(100))),
+                ("fee", Value::Int(3000)),
+                ("active", Value::Bool(true)),
+                ("token", Value::Bytes(Bytes::from_str("0x1234").unwrap())),
+                ("tags", Value::List(vec![])),
+                ("revocable", Value::String("Enabled".to_owned())),
+            ],
+        );
+        let entity_type_ptr = alloc_string(&mut context, "Pool");
+        let id_ptr = alloc_string(&mut context, "pool1");
+
+        let expected_ptr = alloc_entity(
+            &mut context,
+            vec![
+                (
+                    "price",
+                    Value::BigDecimal(BigDecimal::from_str("1").unwrap()),
+                ),
+                ("liquidity", Value::BigInt(BigInt::from(100))),
+                ("fee", Value::Int(3000)),
+                ("active", Value::Bool(true)),
+                ("token", Value::Bytes(Bytes::from_str("0x1234").unwrap())),
+                ("tags", Value::List(vec![])),
+                ("revocable", Value::String("Enabled".to_owned())),
+                ("account", Value::Null),
+            ],
+        );
+        assert!(context
+            .assert_entity_equals(&GasCounter::new(), entity_type_ptr, id_ptr, expected_ptr)
+            .expect("Couldn't call assert_entity_equals."));
+
+        let expected_ptr = alloc_entity(
+            &mut context,
+            vec![
+                (
+                    "price",
+                    Value::BigDecimal(BigDecimal::from_str("1").unwrap()),
+                ),
+                ("liquidity", Value::String("100".to_owned())),
+                ("fee", Value::Int(3000)),
+                ("active", Value::Bool(true)),
+                ("token", Value::Bytes(Bytes::from_str("0x1234").unwrap())),
+                ("tags", Value::List(vec![])),
+                ("account", Value::Bytes(Bytes::from_str("0xabcd").unwrap())),
+            ],
+        );
+
+        accum();
+        let passed = context
+            .assert_entity_equals(&GasCounter::new(), entity_type_ptr, id_ptr, expected_ptr)
+            .expect("Couldn't call assert_entity_equals.");
+
+        unsafe {
+            let logs = LOGS.join("\n");
+            assert!(!passed);
+            assert!(logs.contains("'liquidity': expected String(\"100\") but was BigInt(100)"));
+            assert!(logs.contains("'account': expected Bytes(0xabcd) but the field is missing"));
+            assert!(logs.contains("'revocable': unexpected field with value String(\"Enabled\")"));
+            assert!(!logs.contains("'fee'"));
+            flush();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn assertion_message_is_logged_with_the_next_failure() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Token",
+            "1",
+            vec![("symbol", Value::String("GRT".to_owned()))],
+        );
+        let entity_type_ptr = alloc_string(&mut context, "Token");
+        let id_ptr = alloc_string(&mut context, "1");
+        let message_ptr = alloc_string(&mut context, "token should be removed by the handler");
+        let call_site_ptr = alloc_string(&mut context, "tests/token.test.ts:12:5");
+
+        accum();
+        context
+            .set_assertion_message(&GasCounter::new(), message_ptr, call_site_ptr)
+            .expect("Couldn't call set_assertion_message.");
+        assert!(!context
+            .assert_not_in_store(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_not_in_store."));
+        assert!(!context
+            .assert_not_in_store(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_not_in_store."));
+
+        unsafe {
+            assert_eq!(LOGS.len(), 2);
+            assert!(LOGS[0].contains("token should be removed by the handler"));
+            assert!(LOGS[0].contains("at tests/token.test.ts:12:5"));
+            assert!(LOGS[0].contains("(assert.notInStore)"));
+            assert!(!LOGS[1].contains("token should be removed by the handler"));
+            flush();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn assertion_message_is_cleared_when_a_test_starts() {
+        let module = get_module();
+        let entity_type_ptr = alloc_string(&mut module.instance_ctx_mut(), "Token");
+        let id_ptr = alloc_string(&mut module.instance_ctx_mut(), "1");
+        let message_ptr = alloc_string(&mut module.instance_ctx_mut(), "");
+        let call_site_ptr = alloc_string(&mut module.instance_ctx_mut(), "tests/token.test.ts:3:5");
+
+        store_set(&mut module.instance_ctx_mut(), "Token", "1", vec![]);
+        module
+            .instance_ctx_mut()
+            .set_assertion_message(&GasCounter::new(), message_ptr, call_site_ptr)
+            .expect("Couldn't call set_assertion_message.");
+        module.instance_ctx.on_test_started();
+
+        accum();
+        assert!(!module
+            .instance_ctx_mut()
+            .assert_not_in_store(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_not_in_store."));
+        module
+            .instance_ctx_mut()
+            .set_assertion_message(&GasCounter::new(), message_ptr, call_site_ptr)
+            .expect("Couldn't call set_assertion_message.");
+        assert!(!module
+            .instance_ctx_mut()
+            .assert_not_in_store(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_not_in_store."));
+
+        unsafe {
+            assert_eq!(LOGS.len(), 2);
+            assert!(!LOGS[0].contains("tests/token.test.ts:3:5"));
+            // Only the call site is set, so there is no empty message line before the failure
+            assert!(LOGS[1].contains("(assert.notInStore)"));
+            assert!(!LOGS[1].contains("\n(assert.notInStore)"));
+            assert!(LOGS[1].contains("at tests/token.test.ts:3:5"));
+            flush();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn store_assertions_list_near_matches() {
+        let mut context = get_context();
+
+        for (id, symbol) in [("1", "GRT"), ("2", "ETH"), ("20", "DAI")] {
+            store_set(
+                &mut context,
+                "Token",
+                id,
+                vec![("symbol", Value::String(symbol.to_owned()))],
+            );
+        }
+        let entity_type_ptr = alloc_string(&mut context, "Token");
+
+        accum();
+        assert!(context
+            .assert_entity_count(&GasCounter::new(), entity_type_ptr, 3)
+            .expect("Couldn't call assert_entity_count."));
+        assert!(!context
+            .assert_entity_count(&GasCounter::new(), entity_type_ptr, 2)
+            .expect("Couldn't call assert_entity_count."));
+
+        let id_ptr = alloc_string(&mut context, "2");
+        assert!(context
+            .assert_entity_exists(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_entity_exists."));
+        let id_ptr = alloc_string(&mut context, "21");
+        assert!(!context
+            .assert_entity_exists(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_entity_exists."));
+
+        let fields_ptr = alloc_entity(
+            &mut context,
+            vec![("symbol", Value::String("ETH".to_owned()))],
+        );
+        assert!(context
+            .assert_entity_with_fields(&GasCounter::new(), entity_type_ptr, fields_ptr)
+            .expect("Couldn't call assert_entity_with_fields."));
+        let fields_ptr = alloc_entity(
+            &mut context,
+            vec![
+                ("id", Value::Int(2)),
+                ("symbol", Value::String("GRT".to_owned())),
+            ],
+        );
+        assert!(!context
+            .assert_entity_with_fields(&GasCounter::new(), entity_type_ptr, fields_ptr)
+            .expect("Couldn't call assert_entity_with_fields."));
+
+        unsafe {
+            let logs = LOGS.join("\n");
+            assert!(logs.contains("Expected 2 entities with type 'Token', but found 3: [1, 2, 20]"));
+            assert!(logs.contains("Closest ids: [1, 2, 20]"));
+            assert!(logs.contains("'1':\n  ~ 'id': expected Int(2) but was BigInt(1)"));
+            assert!(logs
+                .contains("'2':\n  ~ 'symbol': expected String(\"GRT\") but was String(\"ETH\")"));
+            flush();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn query_store_resolves_nested_entities() {
+        let mut context = get_context();
+        context
+            .load_fixture("mocks/fixtures/store.yaml")
+            .expect("Couldn't load fixture.");
+
+        let response = context.query_store(
+            r#"{
+                pools {
+                    id
+                    price
+                    liquidity
+                    token
+                    revocable
+                    tags
+                    account { id transfers(orderDirection: desc) { id } }
+                }
+                account(id: "0xABCD") { first: transfers(first: 1, skip: 1) { id } }
+            }"#,
+            "",
+        );
+
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "data": {
+                    "pools": [{
+                        "id": "pool1",
+                        "price": "1.5",
+                        "liquidity": "100000000000000000000",
+                        "token": "0x1234",
+                        "revocable": "Enabled",
+                        "tags": ["stable", "verified"],
+                        "account": {
+                            "id": "0xabcd",
+                            "transfers": [{ "id": "transfer2" }, { "id": "transfer1" }]
+                        }
+                    }],
+                    "account": { "first": [{ "id": "transfer2" }] }
+                }
+            })
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn query_store_filters_and_orders_collections() {
+        let mut context = get_context();
+        for (id, symbol) in [("1", "GRT"), ("2", "ETH"), ("3", "DAI")] {
+            store_set(
+                &mut context,
+                "Token",
+                id,
+                vec![("symbol", Value::String(symbol.to_owned()))],
+            );
+        }
+
+        let response = context.query_store(
+            r#"query($symbols: [String!]) {
+                tokens(where: { symbol_in: $symbols }, orderBy: symbol, orderDirection: desc) {
+                    id
+                    symbol
+                }
+                cheap: tokens(where: { id_gt: 1, symbol_not: "DAI" }) { id }
+            }"#,
+            r#"{ "symbols": ["GRT", "DAI"] }"#,
+        );
+
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "data": {
+                    "tokens": [
+                        { "id": "1", "symbol": "GRT" },
+                        { "id": "3", "symbol": "DAI" }
+                    ],
+                    "cheap": [{ "id": "2" }]
+                }
+            })
+        );
+
+        let response = context.query_store("{ tokens { name } }", "");
+        assert_eq!(
+            response["errors"][0]["message"],
+            "Type `Token` has no field `name`"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn query_store_resolves_fragments_and_nested_filters() {
+        let mut context = get_context();
+        context
+            .load_fixture("mocks/fixtures/store.yaml")
+            .expect("Couldn't load fixture.");
+
+        let response = context.query_store(
+            r#"query Pools($transfer: ID = "transfer1") {
+                pools(where: { account_: { id: "0xabcd" } }) { ...PoolFields }
+                transfers(where: { id: $transfer, from_: { id: "0xabcd" } }) {
+                    ... on Transfer { id }
+                    from @skip(if: true) { id }
+                }
+                none: pools(where: { fee_gt: 3000 }) { id }
+                accounts(where: { or: [
+                    { id: "0x1234" },
+                    { and: [{ id: "0xabcd" }, { transfers_: { id: "transfer2" } }] }
+                ] }) { id }
+            }
+            fragment PoolFields on Pool { id account { id } }"#,
+            "",
+        );
+
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "data": {
+                    "pools": [{ "id": "pool1", "account": { "id": "0xabcd" } }],
+                    "transfers": [{ "id": "transfer1" }],
+                    "none": [],
+                    "accounts": [{ "id": "0xabcd" }]
+                }
+            })
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn query_store_validates_queries_against_the_api_schema() {
+        let mut context = get_context();
+
+        for query in [
+            "{ tokens(limit: 1) { id } }",
+            "{ tokens(where: { symbol_between: \"A\" }) { id } }",
+            "{ tokens { id } } { tokens { symbol } }",
+            "query($id: ID!) { token(id: \"1\") { id } }",
+            "{ token(id: $id) { id } }",
+            "query($id: ID!) { token(id: $id) { id } }",
+            "{ token { id } }",
+            "{ tokens { id { value } } }",
+            "{ accounts { transfers } }",
+        ] {
+            let response = context.query_store(query, "");
+            assert!(
+                response.get("data").is_none() && response["errors"][0]["message"].is_string(),
+                "Query `{}` should fail, but returned {}",
+                query,
+                response
+            );
+        }
+    }
 
-        module
-            .instance_ctx
-            .take()
-            .take()
-            .expect("Couldn't get context from module.")
+    #[test]
+    #[serial]
+    fn query_store_paginates_like_graph_node() {
+        let mut context = get_context();
+        for id in 1..=101 {
+            store_set(&mut context, "Token", &id.to_string(), vec![]);
+        }
+
+        let count = |context: &mut MatchstickInstanceContext<Chain>, arguments: &str| {
+            let response = context.query_store(&format!("{{ tokens{} {{ id }} }}", arguments), "");
+            response["data"]["tokens"]
+                .as_array()
+                .map(|tokens| tokens.len())
+                .ok_or_else(|| response["errors"][0]["message"].to_string())
+        };
+        assert_eq!(count(&mut context, ""), Ok(100));
+        assert_eq!(count(&mut context, "(first: 1000, skip: 100)"), Ok(1));
+        assert!(count(&mut context, "(first: 1001)")
+            .unwrap_err()
+            .contains("The `first` argument must be between 0 and 1000, but is 1001"));
+        assert!(count(&mut context, "(skip: 5001)")
+            .unwrap_err()
+            .contains("The `skip` argument must be between 0 and 5000, but is 5001"));
     }
 
     #[test]
     #[serial]
-    fn log_basic_test() {
+    fn query_store_resolves_block_arguments() {
         let mut context = get_context();
 
-        let message = asc_string_from_str("log message");
-        let pointer = AscPtr::alloc_obj(message, &mut context.wasm_ctx, &GasCounter::new())
-            .expect("Couldn't create pointer.");
+        for (number, symbol) in [(1, "GRT"), (3, "ETH")] {
+            let hash_ptr = alloc_string(&mut context, &format!("0x{:02x}", number));
+            context
+                .start_block(&GasCounter::new(), number, hash_ptr)
+                .expect("Couldn't call start_block.");
+            store_set(
+                &mut context,
+                "Token",
+                "1",
+                vec![("symbol", Value::String(symbol.to_owned()))],
+            );
+            context
+                .commit_block(&GasCounter::new())
+                .expect("Couldn't call commit_block.");
+        }
 
-        accum();
+        let response = context.query_store(
+            r#"{
+                at1: token(id: 1, block: { number: 1 }) { symbol }
+                at2: token(id: 1, block: { number: 2 }) { symbol }
+                byHash: token(id: 1, block: { hash: "0x03" }) { symbol }
+                latest: token(id: 1) { symbol }
+            }"#,
+            "",
+        );
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "data": {
+                    "at1": { "symbol": "GRT" },
+                    "at2": { "symbol": "GRT" },
+                    "byHash": { "symbol": "ETH" },
+                    "latest": { "symbol": "ETH" }
+                }
+            })
+        );
+
+        let response = context.query_store("{ tokens(block: { number: 4 }) { id } }", "");
+        assert_eq!(
+            response["errors"][0]["message"],
+            "Block 4 has not been committed, the last committed block is 3"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn store_operations_are_recorded() {
+        let mut context = get_context();
+
+        for symbol in ["GRT", "GRT", "ETH"] {
+            store_set(
+                &mut context,
+                "Token",
+                "1",
+                vec![("symbol", Value::String(symbol.to_owned()))],
+            );
+        }
+        let entity_type_ptr = alloc_string(&mut context, "Token");
+        let id_ptr = alloc_string(&mut context, "1");
         context
-            .log(&GasCounter::new(), 3, pointer)
-            .expect("Couldn't call log.");
+            .mock_store_get(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call mock_store_get.");
+        context
+            .mock_store_remove(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call mock_store_remove.");
+
+        assert_eq!(
+            context.format_store_operations(),
+            "1. store.set('Token', '1') changed [symbol]\n\
+             2. store.set('Token', '1') changed []\n\
+             3. store.set('Token', '1') changed [symbol]\n\
+             4. store.get('Token', '1')\n\
+             5. store.remove('Token', '1')"
+        );
+
+        let set_ptr = alloc_string(&mut context, "set");
+        let remove_ptr = alloc_string(&mut context, "remove");
+        let any_id_ptr = alloc_string(&mut context, "");
+        assert!(context
+            .assert_store_operation_count(
+                &GasCounter::new(),
+                set_ptr,
+                entity_type_ptr,
+                any_id_ptr,
+                3
+            )
+            .expect("Couldn't call assert_store_operation_count."));
+        assert!(context
+            .assert_store_operation_count(
+                &GasCounter::new(),
+                remove_ptr,
+                entity_type_ptr,
+                id_ptr,
+                1
+            )
+            .expect("Couldn't call assert_store_operation_count."));
 
+        accum();
+        assert!(!context
+            .assert_store_operation_count(&GasCounter::new(), set_ptr, entity_type_ptr, id_ptr, 1)
+            .expect("Couldn't call assert_store_operation_count."));
         unsafe {
-            assert_eq!(LOGS.len(), 1);
-            assert!(LOGS.pop().unwrap().contains("log message"));
+            let logs = LOGS.join("\n");
+            assert!(logs.contains("Expected store.set to be called 1 times for entity type 'Token' and id '1', but it was called 3 times."));
+            assert!(logs.contains("5. store.remove('Token', '1')"));
             flush();
         }
+
+        context
+            .clear_store_operations(&GasCounter::new())
+            .expect("Couldn't call clear_store_operations.");
+        assert!(context.store_operations.is_empty());
     }
 
     #[test]
     #[serial]
-    #[should_panic(expected = "🆘")]
-    fn log_panic_test() {
+    fn revert_to_block_restores_the_committed_store() {
         let mut context = get_context();
 
-        let message = asc_string_from_str("log message");
-        let pointer = AscPtr::alloc_obj(message, &mut context.wasm_ctx, &GasCounter::new())
-            .expect("Couldn't create pointer.");
+        for (number, symbol) in [(1, "GRT"), (2, "ETH")] {
+            let hash_ptr = alloc_string(&mut context, &format!("0x0{}", number));
+            context
+                .start_block(&GasCounter::new(), number, hash_ptr)
+                .expect("Couldn't call start_block.");
+            store_set(
+                &mut context,
+                "Token",
+                "1",
+                vec![("symbol", Value::String(symbol.to_owned()))],
+            );
+            context
+                .commit_block(&GasCounter::new())
+                .expect("Couldn't call commit_block.");
+        }
 
+        let hash_ptr = alloc_string(&mut context, "0x03");
         context
-            .log(&GasCounter::new(), 0, pointer)
-            .expect("Couldn't call log.");
+            .start_block(&GasCounter::new(), 3, hash_ptr)
+            .expect("Couldn't call start_block.");
+        store_set(
+            &mut context,
+            "Token",
+            "2",
+            vec![("symbol", Value::String("DAI".to_owned()))],
+        );
+
+        let entity_type_ptr = alloc_string(&mut context, "Token");
+        let id_ptr = alloc_string(&mut context, "1");
+        let entity_ptr = context
+            .store_get_at_block(&GasCounter::new(), entity_type_ptr, id_ptr, 1)
+            .expect("Couldn't call store_get_at_block.");
+        let entity: HashMap<String, Value> =
+            try_asc_get(&context.wasm_ctx, entity_ptr, &GasCounter::new())
+                .expect("Couldn't get entity.");
+        assert_eq!(entity.get("symbol"), Some(&Value::String("GRT".to_owned())));
+
+        context
+            .revert_to_block(&GasCounter::new(), 1)
+            .expect("Couldn't call revert_to_block.");
+
+        let tokens = context.store.get("Token").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens.get("1").unwrap().get("symbol"),
+            Some(&Value::String("GRT".to_owned()))
+        );
+        assert!(context.revert_to_block(&GasCounter::new(), 2).is_err());
+
+        let hash_ptr = alloc_string(&mut context, "0x02");
+        context
+            .start_block(&GasCounter::new(), 2, hash_ptr)
+            .expect("Block 2 can be started again after the revert.");
+        let hash_ptr = alloc_string(&mut context, "0x01");
+        assert!(context
+            .start_block(&GasCounter::new(), 1, hash_ptr)
+            .is_err());
+
+        // Reverting before the first block brings back the store from before it was started
+        context
+            .revert_to_block(&GasCounter::new(), 0)
+            .expect("Couldn't call revert_to_block.");
+        assert!(context
+            .store
+            .get("Token")
+            .map_or(true, |tokens| tokens.is_empty()));
+        let hash_ptr = alloc_string(&mut context, "0x01");
+        context
+            .start_block(&GasCounter::new(), 1, hash_ptr)
+            .expect("Block 1 can be started again after reverting before it.");
     }
 
     #[test]
     #[serial]
-    fn clear_store_basic_test() {
-        let mut context = get_context();
+    fn blocks_are_cleared_when_a_test_starts() {
+        let module = get_module();
+
+        for _ in 0..2 {
+            module.instance_ctx.on_test_started();
+            for number in [1, 5] {
+                let hash_ptr =
+                    alloc_string(&mut module.instance_ctx_mut(), &format!("0x0{}", number));
+                module
+                    .instance_ctx_mut()
+                    .start_block(&GasCounter::new(), number, hash_ptr)
+                    .expect("Every test can start at block 1.");
+                module
+                    .instance_ctx_mut()
+                    .commit_block(&GasCounter::new())
+                    .expect("Couldn't call commit_block.");
+            }
+            // A block which is started but never committed doesn't break the next test
+            let hash_ptr = alloc_string(&mut module.instance_ctx_mut(), "0x06");
+            module
+                .instance_ctx_mut()
+                .start_block(&GasCounter::new(), 6, hash_ptr)
+                .expect("Couldn't call start_block.");
+        }
 
-        context.store.insert("type".to_owned(), HashMap::new());
+        module
+            .instance_ctx_mut()
+            .clear_store(&GasCounter::new())
+            .expect("Couldn't call clear_store.");
+        let hash_ptr = alloc_string(&mut module.instance_ctx_mut(), "0x01");
+        module
+            .instance_ctx_mut()
+            .start_block(&GasCounter::new(), 1, hash_ptr)
+            .expect("Block 1 can be started after clearStore.");
+    }
 
-        context
+    #[test]
+    #[serial]
+    fn config_fixtures_are_seeded_again_when_a_test_starts() {
+        let module = get_module();
+        module.instance_ctx_mut().fixtures = vec!["mocks/fixtures/tokens.json".to_owned()];
+
+        // As if an `afterEach` hook of the previous test cleared the store
+        module
+            .instance_ctx_mut()
             .clear_store(&GasCounter::new())
-            .expect("Couldn't call clear_store");
+            .expect("Couldn't call clear_store.");
+        module.instance_ctx.on_test_started();
 
-        assert_eq!(context.store.len(), 0);
+        let context = module.instance_ctx();
+        let tokens = context
+            .store
+            .get("Token")
+            .expect("Fixtures should be seeded.");
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens.get("1").unwrap().get("symbol"),
+            Some(&Value::String("GRT".to_owned()))
+        );
     }
 
     #[test]
@@ -166,6 +1365,68 @@ mod tests {
         assert!(result);
     }
 
+    #[test]
+    #[serial]
+    fn assert_field_equals_compares_typed_values() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Pool",
+            "pool1",
+            vec![
+                (
+                    "price",
+                    Value::BigDecimal(BigDecimal::from_str("1.5").unwrap()),
+                ),
+                ("liquidity", Value::BigInt(BigInt::from(100))),
+                ("fee", Value::Int(3000)),
+                ("active", Value::Bool(true)),
+                ("token", Value::Bytes(Bytes::from_str("0x12ab").unwrap())),
+                (
+                    "tags",
+                    Value::List(vec![
+                        Value::String("stable".to_owned()),
+                        Value::String("verified".to_owned()),
+                    ]),
+                ),
+            ],
+        );
+
+        let mut field_equals = |field: &str, expected: &str| -> bool {
+            let entity_type_ptr = alloc_string(&mut context, "Pool");
+            let id_ptr = alloc_string(&mut context, "pool1");
+            let field_name_ptr = alloc_string(&mut context, field);
+            let expected_val_ptr = alloc_string(&mut context, expected);
+
+            context
+                .assert_field_equals(
+                    &GasCounter::new(),
+                    entity_type_ptr,
+                    id_ptr,
+                    field_name_ptr,
+                    expected_val_ptr,
+                )
+                .expect("Couldn't call assert_field_equals.")
+        };
+
+        assert!(field_equals("price", "1.50"));
+        assert!(field_equals("liquidity", "100"));
+        assert!(field_equals("fee", "3000"));
+        assert!(field_equals("token", "0x12AB"));
+        assert!(field_equals("tags", "[stable, verified]"));
+
+        accum();
+        assert!(!field_equals("price", "2"));
+        assert!(!field_equals("tags", "[stable]"));
+        unsafe {
+            let logs = LOGS.join("\n");
+            assert!(logs.contains("to equal 'BigDecimal(2)', but was 'BigDecimal(1.5)'"));
+            assert!(logs.contains("to equal 'List([String(\"stable\")])'"));
+            flush();
+        }
+    }
+
     #[test]
     #[serial]
     fn assert_field_equals_failing_variants() {
@@ -656,31 +1917,343 @@ mod tests {
         let asc_map_pointer = AscPtr::alloc_obj(asc_map, &mut context.wasm_ctx, &GasCounter::new())
             .expect("Couldn't create pointer.");
 
-        context
-            .mock_store_set(
-                &GasCounter::new(),
-                entity_pointer,
-                id_pointer,
-                asc_map_pointer,
-            )
-            .expect("Couldn't call mock_store_set.");
+        context
+            .mock_store_set(
+                &GasCounter::new(),
+                entity_pointer,
+                id_pointer,
+                asc_map_pointer,
+            )
+            .expect("Couldn't call mock_store_set.");
+
+        let inner_map = context
+            .store
+            .get("GraphAccount")
+            .expect("Couldn't get inner map.")
+            .get("graphAccountId")
+            .unwrap();
+        assert_eq!(
+            inner_map
+                .get("nameSignalTransactions")
+                .unwrap()
+                .clone()
+                .as_list()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_set_nullable_and_one_to_one_derived_fields() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Owner",
+            "owner1",
+            vec![("id", Value::from("owner1"))],
+        );
+        store_set(
+            &mut context,
+            "Profile",
+            "profile1",
+            vec![
+                ("id", Value::from("profile1")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+        store_set(
+            &mut context,
+            "Wallet",
+            "wallet2",
+            vec![
+                ("id", Value::from("wallet2")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+        store_set(
+            &mut context,
+            "Wallet",
+            "wallet1",
+            vec![
+                ("id", Value::from("wallet1")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+
+        let owner = context
+            .store
+            .get("Owner")
+            .expect("Couldn't get inner map.")
+            .get("owner1")
+            .unwrap();
+        assert_eq!(owner.get("profile"), Some(&Value::from("profile1")));
+        assert_eq!(
+            owner.get("wallets"),
+            Some(&Value::List(vec![
+                Value::from("wallet2"),
+                Value::from("wallet1")
+            ]))
+        );
+
+        // Re-saving the owner recomputes the derived fields from the stored children
+        store_set(
+            &mut context,
+            "Owner",
+            "owner1",
+            vec![("id", Value::from("owner1"))],
+        );
+
+        let owner = context.store.get("Owner").unwrap().get("owner1").unwrap();
+        assert_eq!(owner.get("profile"), Some(&Value::from("profile1")));
+        assert_eq!(
+            owner.get("wallets"),
+            Some(&Value::List(vec![
+                Value::from("wallet1"),
+                Value::from("wallet2")
+            ]))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_remove_one_to_one_derived_field() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Owner",
+            "owner1",
+            vec![("id", Value::from("owner1"))],
+        );
+        store_set(
+            &mut context,
+            "Profile",
+            "profile1",
+            vec![
+                ("id", Value::from("profile1")),
+                ("owner", Value::from("owner1")),
+            ],
+        );
+
+        let entity_pointer = AscPtr::alloc_obj(
+            asc_string_from_str("Profile"),
+            &mut context.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        let id_pointer = AscPtr::alloc_obj(
+            asc_string_from_str("profile1"),
+            &mut context.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+
+        context
+            .mock_store_remove(&GasCounter::new(), entity_pointer, id_pointer)
+            .expect("Couldn't call mock_store_remove.");
+
+        let owner = context.store.get("Owner").unwrap().get("owner1").unwrap();
+        assert_eq!(owner.get("profile"), Some(&Value::Null));
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_set_derived_fields_with_bytes_ids_and_list_links() {
+        let mut context = get_context();
+
+        let account_id = Value::Bytes(Bytes::from(&[171u8, 205][..]));
+        let other_account_id = Value::Bytes(Bytes::from(&[18u8, 52][..]));
+
+        store_set(
+            &mut context,
+            "Account",
+            "0xabcd",
+            vec![("id", account_id.clone())],
+        );
+        store_set(
+            &mut context,
+            "Account",
+            "0x1234",
+            vec![("id", other_account_id.clone())],
+        );
+        store_set(
+            &mut context,
+            "Transfer",
+            "transfer1",
+            vec![
+                ("id", Value::from("transfer1")),
+                ("from", account_id.clone()),
+            ],
+        );
+        store_set(
+            &mut context,
+            "Vault",
+            "vault1",
+            vec![
+                ("id", Value::from("vault1")),
+                (
+                    "owners",
+                    Value::List(vec![account_id, other_account_id.clone()]),
+                ),
+            ],
+        );
+
+        let accounts = context.store.get("Account").unwrap();
+        assert_eq!(
+            accounts.get("0xabcd").unwrap().get("transfers"),
+            Some(&Value::List(vec![Value::from("transfer1")]))
+        );
+        assert_eq!(
+            accounts.get("0xabcd").unwrap().get("vaults"),
+            Some(&Value::List(vec![Value::from("vault1")]))
+        );
+        assert_eq!(
+            accounts.get("0x1234").unwrap().get("vaults"),
+            Some(&Value::List(vec![Value::from("vault1")]))
+        );
+
+        // Removing the only Account link from the Vault removes the derived relation
+        store_set(
+            &mut context,
+            "Vault",
+            "vault1",
+            vec![
+                ("id", Value::from("vault1")),
+                ("owners", Value::List(vec![other_account_id])),
+            ],
+        );
+        let entity_ptr = AscPtr::alloc_obj(
+            asc_string_from_str("Account"),
+            &mut context.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        let id_ptr = AscPtr::alloc_obj(
+            asc_string_from_str("0xabcd"),
+            &mut context.wasm_ctx,
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        context
+            .mock_store_get(&GasCounter::new(), entity_ptr, id_ptr)
+            .expect("Couldn't call mock_store_get.");
+
+        let accounts = context.store.get("Account").unwrap();
+        assert_eq!(
+            accounts.get("0xabcd").unwrap().get("vaults"),
+            Some(&Value::List(vec![]))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_set_keeps_bytes_ids_typed() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Account",
+            "0xABCD",
+            vec![("id", Value::Bytes(Bytes::from(&[171u8, 205][..])))],
+        );
+
+        let account = context
+            .store
+            .get("Account")
+            .expect("Couldn't get inner map.")
+            .get("0xabcd")
+            .expect("Couldn't get entity by its lowercase hex id.");
+        assert_eq!(
+            account.get("id"),
+            Some(&Value::Bytes(Bytes::from(&[171u8, 205][..])))
+        );
+
+        let entity_type_ptr = alloc_string(&mut context, "Account");
+        let id_ptr = alloc_string(&mut context, "0xAbCd");
+        let field_name_ptr = alloc_string(&mut context, "id");
+        let expected_val_ptr = alloc_string(&mut context, "0xabcd");
+
+        let result = context
+            .assert_field_equals(
+                &GasCounter::new(),
+                entity_type_ptr,
+                id_ptr,
+                field_name_ptr,
+                expected_val_ptr,
+            )
+            .expect("Couldn't call assert_field_equals.");
+        assert!(result);
+
+        let result = context
+            .assert_not_in_store(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call assert_not_in_store.");
+        assert!(!result);
+
+        let entity = context
+            .mock_store_get(&GasCounter::new(), entity_type_ptr, id_ptr)
+            .expect("Couldn't call mock_store_get.");
+        assert!(!entity.is_null());
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_set_validates_ids() {
+        let mut context = get_context();
+
+        // Not a hex string
+        assert!(try_store_set(&mut context, "Account", "0xnothex", vec![]).is_err());
+        // The id field is a string, while the schema declares `id: Bytes!`
+        assert!(try_store_set(
+            &mut context,
+            "Account",
+            "0xabcd",
+            vec![("id", Value::from("0xabcd"))]
+        )
+        .is_err());
+        // The id field doesn't match the id the entity is saved with
+        assert!(try_store_set(
+            &mut context,
+            "Account",
+            "0xabcd",
+            vec![("id", Value::Bytes(Bytes::from(&[18u8, 52][..])))]
+        )
+        .is_err());
+        // Not an Int8
+        assert!(try_store_set(
+            &mut context,
+            "Token",
+            "one",
+            vec![("symbol", Value::from("ONE"))]
+        )
+        .is_err());
 
-        let inner_map = context
+        assert!(!context.store.contains_key("Account"));
+        assert!(!context.store.contains_key("Token"));
+    }
+
+    #[test]
+    #[serial]
+    fn mock_store_set_int8_ids() {
+        let mut context = get_context();
+
+        store_set(
+            &mut context,
+            "Token",
+            "9000000000",
+            vec![("symbol", Value::from("GRT"))],
+        );
+
+        let token = context
             .store
-            .get("GraphAccount")
+            .get("Token")
             .expect("Couldn't get inner map.")
-            .get("graphAccountId")
-            .unwrap();
-        assert_eq!(
-            inner_map
-                .get("nameSignalTransactions")
-                .unwrap()
-                .clone()
-                .as_list()
-                .unwrap()
-                .len(),
-            1
-        );
+            .get("9000000000")
+            .expect("Couldn't get entity.");
+        assert_eq!(token.get("id").unwrap().to_string(), "9000000000");
+        assert!(matches!(token.get("id"), Some(Value::BigInt(_))));
     }
 
     #[test]
@@ -718,7 +2291,7 @@ mod tests {
 
         context.fn_ret_map.insert(
             "0x8920…43e7funcNamefuncName(address):(string,string)val".to_owned(),
-            vec![Token::Bool(false)],
+            MockResult::Return(vec![Token::Bool(false)]),
         );
 
         let contract_name = asc_string_from_str("contractName");
@@ -801,7 +2374,7 @@ mod tests {
 
         context.fn_ret_map.insert(
             "0x8920…43e7funcNamefuncName(address):(string,string)val".to_owned(),
-            REVERTS_IDENTIFIER.clone(),
+            MockResult::Revert(RevertReason::Unknown),
         );
 
         let contract_name = asc_string_from_str("contractName");
@@ -933,11 +2506,14 @@ mod tests {
 
         println!("{:?}", context.fn_ret_map);
 
-        let token = context
+        let token = match context
             .fn_ret_map
             .get("0x8920…43e7funcNamefuncName(address):(string,string)89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7")
-            .unwrap()[0]
-            .clone();
+            .unwrap()
+        {
+            MockResult::Return(tokens) => tokens[0].clone(),
+            result => panic!("Unexpected mocked result: {:?}", result),
+        };
         assert_eq!(
             &token.to_string(),
             "89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7"
@@ -1004,12 +2580,711 @@ mod tests {
             )
             .expect("Couldn't call mock_function.");
 
-        let token = context
+        let result = context
             .fn_ret_map
             .get("0x8920…43e7funcNamefuncName(address):(string,string)89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7")
-            .unwrap()[0]
-            .clone();
-        assert_eq!(token, REVERTS_IDENTIFIER[0]);
+            .unwrap();
+        assert_eq!(result, &MockResult::Revert(RevertReason::Unknown));
+    }
+
+    #[test]
+    #[serial]
+    fn mock_function_with_matchers_precedence() {
+        let mut context = get_context();
+        let contract = "0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7";
+        let other_contract = "0x0000000000000000000000000000000000000001";
+        let holder = Token::Address(Address::from_low_u64_be(1));
+        let other_holder = Token::Address(Address::from_low_u64_be(2));
+        let signature = "balanceOf(address):(uint256)";
+
+        let mock = |context: &mut MatchstickInstanceContext<Chain>,
+                    address: &str,
+                    arg: Token,
+                    matcher: &str,
+                    balance: u64| {
+            let address_ptr = alloc_string(context, address);
+            let fn_name_ptr = alloc_string(context, "balanceOf");
+            let fn_signature_ptr = alloc_string(context, signature);
+            let args_ptr = alloc_tokens(context, &[arg]);
+            let matchers_ptr = asc_new(
+                &mut context.wasm_ctx,
+                &[matcher.to_owned()][..],
+                &GasCounter::new(),
+            )
+            .expect("Couldn't create pointer.");
+            let return_value_ptr = alloc_tokens(context, &[Token::Uint(balance.into())]);
+
+            context.mock_function_with_matchers(
+                &GasCounter::new(),
+                address_ptr,
+                fn_name_ptr,
+                fn_signature_ptr,
+                args_ptr,
+                matchers_ptr,
+                return_value_ptr,
+                AscPtr::new(0),
+            )
+        };
+        let balance_of =
+            |context: &mut MatchstickInstanceContext<Chain>, address: &str, arg: &Token| {
+                call_contract(context, address, "balanceOf", signature, &[arg.clone()])
+                    .expect("Couldn't call ethereum_call.")
+                    .expect("The call shouldn't revert.")
+            };
+
+        mock(&mut context, "*", Token::Bool(false), "type:address", 1)
+            .expect("Couldn't call mock_function_with_matchers.");
+        mock(&mut context, contract, Token::Bool(false), "any", 2)
+            .expect("Couldn't call mock_function_with_matchers.");
+        mock(
+            &mut context,
+            contract,
+            Token::Array(vec![holder.clone()]),
+            "oneOf",
+            3,
+        )
+        .expect("Couldn't call mock_function_with_matchers.");
+
+        // Mocks with fewer wildcards win, the latest one wins a tie
+        assert_eq!(
+            balance_of(&mut context, other_contract, &holder),
+            vec![Token::Uint(1u64.into())]
+        );
+        assert_eq!(
+            balance_of(&mut context, contract, &other_holder),
+            vec![Token::Uint(2u64.into())]
+        );
+        assert_eq!(
+            balance_of(&mut context, contract, &holder),
+            vec![Token::Uint(3u64.into())]
+        );
+
+        // A mock without wildcards wins over all the mocks with wildcards
+        mock(&mut context, contract, holder.clone(), "eq", 4)
+            .expect("Couldn't call mock_function_with_matchers.");
+        assert_eq!(
+            balance_of(&mut context, contract, &holder),
+            vec![Token::Uint(4u64.into())]
+        );
+
+        assert!(mock(&mut context, contract, Token::Bool(false), "eq", 5).is_err());
+        assert!(mock(&mut context, contract, Token::Bool(false), "someOf", 5).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn contract_calls_are_recorded_and_asserted() {
+        let mut context = get_context();
+        let contract = Address::from_str("0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7")
+            .expect("Couldn't create Address.");
+        let contract_str = "0x89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7";
+        let holder = Token::Address(Address::from_low_u64_be(1));
+        let other_holder = Token::Address(Address::from_low_u64_be(2));
+        let signature = "balanceOf(address):(uint256)";
+
+        let address_ptr: AscPtr<TypedArray<u8>> =
+            asc_new(&mut context.wasm_ctx, &contract, &GasCounter::new())
+                .expect("Couldn't create pointer.");
+        let fn_name_ptr = alloc_string(&mut context, "balanceOf");
+        let fn_signature_ptr = alloc_string(&mut context, signature);
+        let args_ptr = alloc_tokens(&mut context, &[holder.clone()]);
+        let return_value_ptr = alloc_tokens(&mut context, &[Token::Uint(10u64.into())]);
+        context
+            .mock_function(
+                &GasCounter::new(),
+                address_ptr.wasm_ptr(),
+                fn_name_ptr,
+                fn_signature_ptr,
+                args_ptr,
+                return_value_ptr,
+                AscPtr::new(0),
+            )
+            .expect("Couldn't call mock_function.");
+
+        for (fn_name, signature, matcher) in [
+            ("balanceOf", signature, "any"),
+            ("totalSupply", "totalSupply():(uint256)", ""),
+        ] {
+            let address_ptr = alloc_string(&mut context, "*");
+            let fn_name_ptr = alloc_string(&mut context, fn_name);
+            let fn_signature_ptr = alloc_string(&mut context, signature);
+            let (args, matchers) = if matcher.is_empty() {
+                (vec![], vec![])
+            } else {
+                (vec![Token::Bool(false)], vec![matcher.to_owned()])
+            };
+            let args_ptr = alloc_tokens(&mut context, &args);
+            let matchers_ptr = asc_new(
+                &mut context.wasm_ctx,
+                matchers.as_slice(),
+                &GasCounter::new(),
+            )
+            .expect("Couldn't create pointer.");
+            let return_value_ptr = alloc_tokens(&mut context, &[Token::Uint(1u64.into())]);
+            context
+                .mock_function_with_matchers(
+                    &GasCounter::new(),
+                    address_ptr,
+                    fn_name_ptr,
+                    fn_signature_ptr,
+                    args_ptr,
+                    matchers_ptr,
+                    return_value_ptr,
+                    AscPtr::new(0),
+                )
+                .expect("Couldn't call mock_function_with_matchers.");
+        }
+
+        for holder in [&holder, &holder, &other_holder] {
+            call_contract(
+                &mut context,
+                contract_str,
+                "balanceOf",
+                signature,
+                &[holder.clone()],
+            )
+            .expect("Couldn't call ethereum_call.");
+        }
+        assert!(call_contract(&mut context, contract_str, "name", "name():(string)", &[]).is_err());
+
+        assert_eq!(context.contract_calls.len(), 4);
+        assert!(!context.contract_calls[3].mocked);
+        assert_eq!(
+            context.unused_mocks(),
+            vec!["totalSupply():(uint256) on any contract with []".to_owned()]
+        );
+
+        let call_count = |context: &mut MatchstickInstanceContext<Chain>,
+                          address: &str,
+                          args: &[Token],
+                          expected_count: u32| {
+            let address_ptr = alloc_string(context, address);
+            let fn_signature_ptr = alloc_string(context, signature);
+            let args_ptr = alloc_tokens(context, args);
+            context
+                .assert_contract_call_count(
+                    &GasCounter::new(),
+                    address_ptr,
+                    fn_signature_ptr,
+                    args_ptr,
+                    expected_count,
+                )
+                .expect("Couldn't call assert_contract_call_count.")
+        };
+        assert!(call_count(&mut context, contract_str, &[holder.clone()], 2));
+        assert!(!call_count(
+            &mut context,
+            contract_str,
+            &[holder.clone()],
+            3
+        ));
+        assert!(call_count(&mut context, "", &[other_holder.clone()], 1));
+
+        let not_called = |context: &mut MatchstickInstanceContext<Chain>, signature: &str| {
+            let address_ptr = alloc_string(context, contract_str);
+            let fn_signature_ptr = alloc_string(context, signature);
+            context
+                .assert_contract_not_called(&GasCounter::new(), address_ptr, fn_signature_ptr)
+                .expect("Couldn't call assert_contract_not_called.")
+        };
+        assert!(not_called(&mut context, "totalSupply():(uint256)"));
+        assert!(!not_called(&mut context, signature));
+    }
+
+    #[test]
+    #[serial]
+    fn mock_function_once_consumes_sequence() {
+        let mut context = get_context();
+        let contract = Address::from_low_u64_be(10);
+        let contract_str = format!("{:?}", contract);
+        let signature = "totalSupply():(uint256)";
+
+        // Mocks `totalSupply` with `mockFunctionOnce` or `mockFunction`, `None` reverts
+        let mock =
+            |context: &mut MatchstickInstanceContext<Chain>, once: bool, supply: Option<u64>| {
+                let address_ptr: AscPtr<TypedArray<u8>> =
+                    asc_new(&mut context.wasm_ctx, &contract, &GasCounter::new())
+                        .expect("Couldn't create pointer.");
+                let fn_name_ptr = alloc_string(context, "totalSupply");
+                let fn_signature_ptr = alloc_string(context, signature);
+                let args_ptr = alloc_tokens(context, &[]);
+                let return_value: Vec<Token> = supply
+                    .map(|supply| vec![Token::Uint(supply.into())])
+                    .unwrap_or_default();
+                let return_value_ptr = alloc_tokens(context, &return_value);
+                let reverts_ptr = AscPtr::new(u32::from(supply.is_none()));
+
+                let result = if once {
+                    context.mock_function_once(
+                        &GasCounter::new(),
+                        address_ptr.wasm_ptr(),
+                        fn_name_ptr,
+                        fn_signature_ptr,
+                        args_ptr,
+                        return_value_ptr,
+                        reverts_ptr,
+                    )
+                } else {
+                    context.mock_function(
+                        &GasCounter::new(),
+                        address_ptr.wasm_ptr(),
+                        fn_name_ptr,
+                        fn_signature_ptr,
+                        args_ptr,
+                        return_value_ptr,
+                        reverts_ptr,
+                    )
+                };
+                result.expect("Couldn't mock totalSupply.");
+            };
+        let when_exhausted = |context: &mut MatchstickInstanceContext<Chain>, behavior: &str| {
+            let address_ptr: AscPtr<TypedArray<u8>> =
+                asc_new(&mut context.wasm_ctx, &contract, &GasCounter::new())
+                    .expect("Couldn't create pointer.");
+            let fn_name_ptr = alloc_string(context, "totalSupply");
+            let fn_signature_ptr = alloc_string(context, signature);
+            let args_ptr = alloc_tokens(context, &[]);
+            let behavior_ptr = alloc_string(context, behavior);
+
+            context
+                .mock_function_when_exhausted(
+                    &GasCounter::new(),
+                    address_ptr.wasm_ptr(),
+                    fn_name_ptr,
+                    fn_signature_ptr,
+                    args_ptr,
+                    behavior_ptr,
+                )
+                .expect("Couldn't call mock_function_when_exhausted.");
+        };
+        let total_supply = |context: &mut MatchstickInstanceContext<Chain>| {
+            call_contract(context, &contract_str, "totalSupply", signature, &[])
+                .map(|result| result.map(|tokens| tokens[0].clone().into_uint().unwrap().as_u64()))
+        };
+
+        mock(&mut context, false, Some(100));
+        mock(&mut context, true, Some(1));
+        mock(&mut context, true, None);
+        mock(&mut context, true, Some(2));
+
+        assert_eq!(total_supply(&mut context).unwrap(), Some(1));
+        assert_eq!(total_supply(&mut context).unwrap(), None);
+        assert_eq!(total_supply(&mut context).unwrap(), Some(2));
+        // Falls back to `mockFunction` by default
+        assert_eq!(total_supply(&mut context).unwrap(), Some(100));
+
+        when_exhausted(&mut context, "cycle");
+        assert_eq!(total_supply(&mut context).unwrap(), Some(1));
+        when_exhausted(&mut context, "repeatLast");
+        assert_eq!(total_supply(&mut context).unwrap(), Some(2));
+        when_exhausted(&mut context, "revert");
+        assert_eq!(total_supply(&mut context).unwrap(), None);
+        when_exhausted(&mut context, "fail");
+        assert!(total_supply(&mut context).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn mock_function_is_validated_against_manifest_abis() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let mut context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+
+        let contract = Address::from_low_u64_be(10);
+        let contract_str = format!("{:?}", contract);
+        let mock = |context: &mut MatchstickInstanceContext<Chain>,
+                    fn_name: &str,
+                    fn_signature: &str,
+                    args: &[Token]| {
+            let address_ptr: AscPtr<TypedArray<u8>> =
+                asc_new(&mut context.wasm_ctx, &contract, &GasCounter::new())
+                    .expect("Couldn't create pointer.");
+            let fn_name_ptr = alloc_string(context, fn_name);
+            let fn_signature_ptr = alloc_string(context, fn_signature);
+            let args_ptr = alloc_tokens(context, args);
+            let return_value_ptr = alloc_tokens(context, &[Token::Uint(7u64.into())]);
+
+            context.mock_function(
+                &GasCounter::new(),
+                address_ptr.wasm_ptr(),
+                fn_name_ptr,
+                fn_signature_ptr,
+                args_ptr,
+                return_value_ptr,
+                AscPtr::new(0),
+            )
+        };
+        let owner = Token::Address(Address::from_low_u64_be(1));
+
+        // The signature is derived from the ABI, the overload is picked by the arguments
+        mock(&mut context, "balanceOf", "", &[owner.clone()])
+            .expect("Couldn't call mock_function.");
+        let result = call_contract(
+            &mut context,
+            &contract_str,
+            "balanceOf",
+            "balanceOf(address):(uint256)",
+            &[owner.clone()],
+        )
+        .expect("Couldn't call ethereum_call.");
+        assert_eq!(result, Some(vec![Token::Uint(7u64.into())]));
+
+        mock(
+            &mut context,
+            "gravatarToOwner",
+            "gravatarToOwner(uint256):(address)",
+            &[Token::Uint(1u64.into())],
+        )
+        .expect("Couldn't call mock_function.");
+
+        let typo = mock(
+            &mut context,
+            "gravatarToOwnr",
+            "",
+            &[Token::Uint(1u64.into())],
+        )
+        .expect_err("A function missing from the ABIs should fail.");
+        assert!(typo.to_string().contains("Did you mean: gravatarToOwner"));
+
+        // Stale signatures and unknown functions are only logged, unless `strictMocks` is set
+        accum();
+        mock(
+            &mut context,
+            "getGravatar",
+            "getGravatar(address):(string)",
+            &[owner.clone()],
+        )
+        .expect("A signature not matching the ABI should only be logged.");
+        mock(
+            &mut context,
+            "burn",
+            "burn(uint256):(bool)",
+            &[Token::Uint(1u64.into())],
+        )
+        .expect("A function missing from the ABIs should only be logged.");
+        unsafe {
+            let logs = LOGS.join("\n");
+            assert!(logs.contains(
+                "Closest declared signature: getGravatar(address):(string,string) (Gravity)"
+            ));
+            assert!(logs.contains("Function `burn` is not declared in the ABIs of the manifest"));
+            flush();
+        }
+
+        STRICT_MOCKS.with(|strict| *strict.borrow_mut() = true);
+        let stale = mock(
+            &mut context,
+            "getGravatar",
+            "getGravatar(address):(string)",
+            &[owner],
+        );
+        let unknown = mock(
+            &mut context,
+            "burn",
+            "burn(uint256):(bool)",
+            &[Token::Uint(1u64.into())],
+        );
+        STRICT_MOCKS.with(|strict| *strict.borrow_mut() = false);
+
+        let stale = stale.expect_err("A signature not matching the ABI should fail.");
+        assert!(stale
+            .to_string()
+            .contains("getGravatar(address):(string,string) (Gravity)"));
+        assert!(unknown.is_err());
+    }
+
+    /// Serves the JSON-RPC responses, one per connection, like a local node would.
+    fn serve_rpc_responses(responses: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind listener.");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().expect("Couldn't accept connection.");
+                let mut request = vec![];
+                let mut buffer = [0; 1024];
+                // Reads the request until the JSON body is complete
+                while !String::from_utf8_lossy(&request).ends_with('}') {
+                    let read = stream.read(&mut buffer).expect("Couldn't read request.");
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .expect("Couldn't write response.");
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    #[serial]
+    fn cassette_records_and_replays_unmocked_calls() {
+        let mut context = get_context();
+        let cassette_path = std::env::temp_dir().join("matchstick_cassette_test.json");
+        let _ = std::fs::remove_file(&cassette_path);
+
+        let contract = "0x2E645469f354BB4F5c8a05B3b30A929361cf77eC";
+        let signature = "gravatarToOwner(uint256):(address)";
+        let owner = Address::from_low_u64_be(1);
+        let revert_data = Bytes::from(
+            [
+                vec![0x08, 0xc3, 0x79, 0xa0],
+                ethabi::encode(&[Token::String("No gravatar".to_owned())]),
+            ]
+            .concat()
+            .as_slice(),
+        );
+        let url = serve_rpc_responses(vec![
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": Bytes::from(ethabi::encode(&[Token::Address(owner)]).as_slice()).to_string(),
+            })
+            .to_string(),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": 3, "message": "execution reverted: No gravatar", "data": revert_data.to_string() },
+            })
+            .to_string(),
+            // Errors which are not reverts fail the call, even if the message mentions a revert
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": { "code": -32000, "message": "header not found, cannot revert to block" },
+            })
+            .to_string(),
+        ]);
+
+        context.cassette = Some(
+            Cassette::load(&cassette_path, Some(url), Some(100)).expect("Couldn't load cassette."),
+        );
+        let record = |context: &mut MatchstickInstanceContext<Chain>, id: u64| {
+            call_contract(
+                context,
+                contract,
+                "gravatarToOwner",
+                signature,
+                &[Token::Uint(id.into())],
+            )
+            .expect("Couldn't call ethereum_call.")
+        };
+        assert_eq!(record(&mut context, 1), Some(vec![Token::Address(owner)]));
+        assert_eq!(record(&mut context, 2), None);
+        assert_eq!(
+            context.contract_calls[1].revert,
+            Some(RevertReason::Message("No gravatar".to_owned()))
+        );
+        assert!(call_contract(
+            &mut context,
+            contract,
+            "gravatarToOwner",
+            signature,
+            &[Token::Uint(3u64.into())],
+        )
+        .is_err());
+
+        // Replays the recorded calls without a node
+        context.cassette =
+            Some(Cassette::load(&cassette_path, None, Some(100)).expect("Couldn't load cassette."));
+        assert_eq!(record(&mut context, 1), Some(vec![Token::Address(owner)]));
+        assert_eq!(record(&mut context, 2), None);
+        assert!(call_contract(
+            &mut context,
+            contract,
+            "gravatarToOwner",
+            signature,
+            &[Token::Uint(3u64.into())],
+        )
+        .is_err());
+
+        // Calls recorded at another block are not replayed
+        context.cassette =
+            Some(Cassette::load(&cassette_path, None, None).expect("Couldn't load cassette."));
+        assert!(call_contract(
+            &mut context,
+            contract,
+            "gravatarToOwner",
+            signature,
+            &[Token::Uint(1u64.into())],
+        )
+        .is_err());
+
+        std::fs::remove_file(&cassette_path).expect("Couldn't remove cassette.");
+    }
+
+    #[test]
+    #[serial]
+    fn created_data_sources_are_recorded_and_asserted() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let mut context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+
+        let wallet = "0x89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7";
+        let alloc_params = |context: &mut MatchstickInstanceContext<Chain>, params: &[&str]| {
+            let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+            asc_new(&mut context.wasm_ctx, params.as_slice(), &GasCounter::new())
+                .expect("Couldn't create pointer.")
+        };
+
+        let name_ptr = alloc_string(&mut context, "GraphTokenLockWallet");
+        let params_ptr = alloc_params(&mut context, &[wallet]);
+        context
+            .mock_data_source_create(&GasCounter::new(), name_ptr, params_ptr)
+            .expect("Couldn't call mock_data_source_create.");
+        let context_ptr = alloc_entity(&mut context, vec![("beneficiary", Value::from("Alice"))]);
+        context
+            .mock_data_source_create_with_context(
+                &GasCounter::new(),
+                name_ptr,
+                params_ptr,
+                context_ptr,
+            )
+            .expect("Couldn't call mock_data_source_create_with_context.");
+
+        let typo_ptr = alloc_string(&mut context, "GraphTokenLockWalet");
+        assert!(context
+            .mock_data_source_create(&GasCounter::new(), typo_ptr, params_ptr)
+            .is_err());
+        assert_eq!(context.created_data_sources.len(), 2);
+
+        assert!(context
+            .assert_data_source_created(&GasCounter::new(), name_ptr, params_ptr)
+            .expect("Couldn't call assert_data_source_created."));
+        let other_params_ptr = alloc_params(&mut context, &["0x0"]);
+        assert!(!context
+            .assert_data_source_created(&GasCounter::new(), name_ptr, other_params_ptr)
+            .expect("Couldn't call assert_data_source_created."));
+
+        assert!(context
+            .assert_data_source_created_with_context(
+                &GasCounter::new(),
+                name_ptr,
+                params_ptr,
+                context_ptr
+            )
+            .expect("Couldn't call assert_data_source_created_with_context."));
+        let other_context_ptr =
+            alloc_entity(&mut context, vec![("beneficiary", Value::from("Bob"))]);
+        assert!(!context
+            .assert_data_source_created_with_context(
+                &GasCounter::new(),
+                name_ptr,
+                params_ptr,
+                other_context_ptr
+            )
+            .expect("Couldn't call assert_data_source_created_with_context."));
+
+        let all_ptr = alloc_string(&mut context, "");
+        assert!(context
+            .assert_data_source_count(&GasCounter::new(), all_ptr, 2)
+            .expect("Couldn't call assert_data_source_count."));
+        assert!(!context
+            .assert_data_source_count(&GasCounter::new(), name_ptr, 1)
+            .expect("Couldn't call assert_data_source_count."));
+    }
+
+    #[test]
+    #[serial]
+    fn mock_function_revert_records_reason() {
+        let mut context = get_context();
+        let contract = Address::from_low_u64_be(10);
+        let contract_str = format!("{:?}", contract);
+        let signature = "transfer(address,uint256):(bool)";
+        let args = vec![
+            Token::Address(Address::from_low_u64_be(1)),
+            Token::Uint(5u64.into()),
+        ];
+
+        let mock_revert =
+            |context: &mut MatchstickInstanceContext<Chain>, kind: &str, data: &str, once: bool| {
+                let address_ptr: AscPtr<TypedArray<u8>> =
+                    asc_new(&mut context.wasm_ctx, &contract, &GasCounter::new())
+                        .expect("Couldn't create pointer.");
+                let fn_name_ptr = alloc_string(context, "transfer");
+                let fn_signature_ptr = alloc_string(context, signature);
+                let args_ptr = alloc_tokens(context, &args);
+                let kind_ptr = alloc_string(context, kind);
+                let data_ptr = alloc_string(context, data);
+
+                context.mock_function_revert(
+                    &GasCounter::new(),
+                    address_ptr.wasm_ptr(),
+                    fn_name_ptr,
+                    fn_signature_ptr,
+                    args_ptr,
+                    kind_ptr,
+                    data_ptr,
+                    AscPtr::new(u32::from(once)),
+                )
+            };
+
+        mock_revert(&mut context, "reason", "insufficient balance", false)
+            .expect("Couldn't call mock_function_revert.");
+        mock_revert(&mut context, "outOfGas", "", true)
+            .expect("Couldn't call mock_function_revert.");
+        assert!(mock_revert(&mut context, "customError", "0x1234", false).is_err());
+        assert!(mock_revert(&mut context, "panic", "", false).is_err());
+
+        for _ in 0..2 {
+            let result = call_contract(&mut context, &contract_str, "transfer", signature, &args)
+                .expect("Couldn't call ethereum_call.");
+            assert_eq!(result, None);
+        }
+        assert_eq!(
+            context.contract_calls[0].revert,
+            Some(RevertReason::OutOfGas)
+        );
+        assert_eq!(
+            context.contract_calls[1].revert,
+            Some(RevertReason::Message("insufficient balance".to_owned()))
+        );
+
+        mock_revert(
+            &mut context,
+            "customError",
+            "0xCF4791810000000000000000000000000000000000000000000000000000000000000005",
+            false,
+        )
+        .expect("Couldn't call mock_function_revert.");
+        call_contract(&mut context, &contract_str, "transfer", signature, &args)
+            .expect("Couldn't call ethereum_call.");
+        assert_eq!(
+            context.contract_calls[2].revert.as_ref().unwrap().to_string(),
+            "with custom error 0xcf479181 (0xcf4791810000000000000000000000000000000000000000000000000000000000000005)"
+        );
+
+        let reverted =
+            |context: &mut MatchstickInstanceContext<Chain>, signature: &str, reason: &str| {
+                let address_ptr = alloc_string(context, &contract_str);
+                let fn_signature_ptr = alloc_string(context, signature);
+                let reason_ptr = alloc_string(context, reason);
+                context
+                    .assert_contract_call_reverted(
+                        &GasCounter::new(),
+                        address_ptr,
+                        fn_signature_ptr,
+                        reason_ptr,
+                    )
+                    .expect("Couldn't call assert_contract_call_reverted.")
+            };
+        assert!(reverted(&mut context, signature, ""));
+        assert!(reverted(&mut context, signature, "0xcf479181"));
+        assert!(!reverted(&mut context, signature, "insufficient balance"));
+        assert!(!reverted(&mut context, "balanceOf(address):(uint256)", ""));
+
+        mock_revert(&mut context, "reason", "insufficient balance", false)
+            .expect("Couldn't call mock_function_revert.");
+        call_contract(&mut context, &contract_str, "transfer", signature, &args)
+            .expect("Couldn't call ethereum_call.");
+        assert!(reverted(&mut context, signature, "insufficient balance"));
     }
 
     #[test]
@@ -1095,6 +3370,141 @@ mod tests {
         );
     }
 
+    #[test]
+    #[serial]
+    fn use_created_data_source_sets_return_values() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let mut context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+
+        let wallet = Address::from_low_u64_be(7);
+        let wallet_ptr = alloc_string(&mut context, &format!("{:?}", wallet));
+        let name_ptr = alloc_string(&mut context, "GraphTokenLockWallet");
+        let params_ptr = asc_new(
+            &mut context.wasm_ctx,
+            &[format!("{:?}", wallet)][..],
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        let context_ptr = alloc_entity(&mut context, vec![("beneficiary", Value::from("Alice"))]);
+        context
+            .mock_data_source_create_with_context(
+                &GasCounter::new(),
+                name_ptr,
+                params_ptr,
+                context_ptr,
+            )
+            .expect("Couldn't call mock_data_source_create_with_context.");
+
+        let any_ptr = alloc_string(&mut context, "");
+        context
+            .use_created_data_source(&GasCounter::new(), any_ptr, wallet_ptr)
+            .expect("Couldn't call use_created_data_source.");
+        let (address, network, data_source_context) = get_address_network_context(&mut context);
+        assert_eq!(address, wallet);
+        assert_eq!(network, "mainnet");
+        assert_eq!(
+            data_source_context.get("beneficiary"),
+            Some(&Value::from("Alice"))
+        );
+
+        let other_ptr = alloc_string(&mut context, &format!("{:?}", Address::from_low_u64_be(8)));
+        assert!(context
+            .use_created_data_source(&GasCounter::new(), name_ptr, other_ptr)
+            .is_err());
+
+        context
+            .reset_data_source_return_values(&GasCounter::new())
+            .expect("Couldn't call reset_data_source_return_values.");
+        // The values of the only data source in the manifest are the defaults
+        let (address, _, data_source_context) = get_address_network_context(&mut context);
+        assert_eq!(
+            address,
+            Address::from_str("0x2E645469f354BB4F5c8a05B3b30A929361cf77eC").unwrap()
+        );
+        assert!(data_source_context.is_empty());
+
+        // The data sources created from templates are on the selected network
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        NETWORK.with(|network| *network.borrow_mut() = Some("goerli".to_owned()));
+        let mut context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+        NETWORK.with(|network| *network.borrow_mut() = None);
+
+        let wallet_ptr = alloc_string(&mut context, &format!("{:?}", wallet));
+        let name_ptr = alloc_string(&mut context, "GraphTokenLockWallet");
+        let params_ptr = asc_new(
+            &mut context.wasm_ctx,
+            &[format!("{:?}", wallet)][..],
+            &GasCounter::new(),
+        )
+        .expect("Couldn't create pointer.");
+        context
+            .mock_data_source_create(&GasCounter::new(), name_ptr, params_ptr)
+            .expect("Couldn't call mock_data_source_create.");
+        context
+            .use_created_data_source(&GasCounter::new(), name_ptr, wallet_ptr)
+            .expect("Couldn't call use_created_data_source.");
+        let (_, network, _) = get_address_network_context(&mut context);
+        assert_eq!(network, "goerli");
+    }
+
+    #[test]
+    #[serial]
+    fn manifest_is_loaded_once_and_shared_by_instances() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let context = get_context();
+        let other_context = get_context();
+        NETWORK.with(|network| *network.borrow_mut() = Some("goerli".to_owned()));
+        let goerli_context = get_context();
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+        NETWORK.with(|network| *network.borrow_mut() = None);
+
+        assert!(std::rc::Rc::ptr_eq(
+            &context.manifest,
+            &other_context.manifest
+        ));
+        assert!(!std::rc::Rc::ptr_eq(
+            &context.manifest,
+            &goerli_context.manifest
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn data_source_values_default_to_manifest_and_network() {
+        MANIFEST_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/subgraph.yaml"));
+        let mut context = get_context();
+        context.set_test_file(PathBuf::from("tests/gravity.test.ts"));
+
+        let (address, network, _) = get_address_network_context(&mut context);
+        assert_eq!(
+            address,
+            Address::from_str("0x2E645469f354BB4F5c8a05B3b30A929361cf77eC").unwrap()
+        );
+        assert_eq!(network, "mainnet");
+
+        NETWORKS_LOCATION
+            .with(|path| *path.borrow_mut() = PathBuf::from("./mocks/yamls/networks.json"));
+        NETWORK.with(|network| *network.borrow_mut() = Some("goerli".to_owned()));
+        let mut context = get_context();
+        context.set_test_file(PathBuf::from("tests/gravity.test.ts"));
+        MANIFEST_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+        NETWORKS_LOCATION.with(|path| *path.borrow_mut() = PathBuf::new());
+        NETWORK.with(|network| *network.borrow_mut() = None);
+
+        let (address, network, _) = get_address_network_context(&mut context);
+        assert_eq!(
+            address,
+            Address::from_str("0x90cBa2Bbb19ecc291A12066Fd8329D65FA1f1947").unwrap()
+        );
+        assert_eq!(network, "goerli");
+    }
+
     fn get_address_network_context(
         context: &mut MatchstickInstanceContext<Chain>,
     ) -> (Address, String, HashMap<String, Value>) {
@@ -1244,6 +3654,14 @@ mod tests {
         let gravatar_map = context.store.get("Gravatar").expect("No such key in map");
 
         assert_eq!(gravatar_map.len(), 3);
+        assert_eq!(
+            context
+                .store_operations
+                .iter()
+                .filter(|operation| operation.kind.to_string() == "store.set")
+                .count(),
+            3
+        );
 
         let gravatar_1 = gravatar_map.get("1").expect("No such key in map");
         let gravatar_2 = gravatar_map.get("2").expect("No such key in map");